@@ -0,0 +1,128 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Field, Fields, LitStr, Result, Type};
+
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<LitStr>,
+    default: bool,
+    flatten: bool,
+    try_from: Option<Type>,
+}
+
+impl FieldAttrs {
+    fn parse(field: &Field) -> Result<Self> {
+        let mut attrs = FieldAttrs::default();
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("mysql"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    attrs.rename = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("default") {
+                    attrs.default = true;
+                } else if meta.path.is_ident("flatten") {
+                    attrs.flatten = true;
+                } else if meta.path.is_ident("try_from") {
+                    let ty: LitStr = meta.value()?.parse()?;
+                    attrs.try_from = Some(ty.parse()?);
+                } else {
+                    return Err(meta.error(
+                        "unknown mysql attribute, expected `rename`, `default`, `flatten` or `try_from`",
+                    ));
+                }
+                Ok(())
+            })?;
+        }
+        if attrs.flatten && (attrs.rename.is_some() || attrs.default || attrs.try_from.is_some()) {
+            return Err(Error::new_spanned(
+                field,
+                "`flatten` cannot be combined with other mysql attributes",
+            ));
+        }
+        Ok(attrs)
+    }
+}
+
+pub(crate) fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "FromRow can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "FromRow can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut values = Vec::new();
+    for field in fields {
+        let attrs = FieldAttrs::parse(field)?;
+        let ident = field.ident.as_ref().expect("named field");
+        let ty = &field.ty;
+        let column = attrs
+            .rename
+            .map(|rename| rename.value())
+            .unwrap_or_else(|| {
+                let name = ident.to_string();
+                name.strip_prefix("r#").map(str::to_string).unwrap_or(name)
+            });
+
+        let value = match (attrs.flatten, attrs.try_from, attrs.default) {
+            (true, _, _) => quote! {
+                <#ty as ::mysql::__private::FromRow>::from_row_opt(row.clone()).ok()
+            },
+            (false, Some(source), false) => quote! {
+                ::mysql::__private::get::<#source>(row, #column).and_then(|value| {
+                    <#ty as ::std::convert::TryFrom<#source>>::try_from(value).ok()
+                })
+            },
+            (false, Some(source), true) => quote! {
+                ::mysql::__private::get_opt::<#source>(row, #column).and_then(|value| match value {
+                    ::std::option::Option::Some(value) => {
+                        <#ty as ::std::convert::TryFrom<#source>>::try_from(value).ok()
+                    }
+                    ::std::option::Option::None => {
+                        ::std::option::Option::Some(<#ty as ::std::default::Default>::default())
+                    }
+                })
+            },
+            (false, None, false) => quote! {
+                ::mysql::__private::get::<#ty>(row, #column)
+            },
+            (false, None, true) => quote! {
+                ::mysql::__private::get_opt::<#ty>(row, #column)
+                    .map(::std::option::Option::unwrap_or_default)
+            },
+        };
+        values.push(quote! { #ident: #value? });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::mysql::__private::FromRow for #name #ty_generics #where_clause {
+            fn from_row_opt(
+                row: ::mysql::Row,
+            ) -> ::std::result::Result<Self, ::mysql::FromRowError>
+            where
+                Self: ::std::marker::Sized,
+            {
+                let value = (|row: &::mysql::Row| -> ::std::option::Option<Self> {
+                    ::std::option::Option::Some(Self { #(#values,)* })
+                })(&row);
+                value.ok_or_else(|| ::mysql::FromRowError(row))
+            }
+        }
+    })
+}
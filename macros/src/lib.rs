@@ -0,0 +1,57 @@
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, ItemFn, LitStr};
+
+mod from_row;
+mod migrations;
+mod test;
+
+/// Derives `mysql::FromRow` by matching columns to struct fields by name.
+///
+/// Field attributes:
+///
+/// * `#[mysql(rename = "column")]` reads the field from a differently named column.
+/// * `#[mysql(default)]` uses `Default::default()` if the column is missing or `NULL`.
+/// * `#[mysql(flatten)]` builds the field from the same row using its own `FromRow` impl.
+/// * `#[mysql(try_from = "Type")]` reads the column as `Type` and converts it with `TryFrom`.
+///
+/// `Option<T>` fields map `NULL` to `None`.
+#[proc_macro_derive(FromRow, attributes(mysql))]
+pub fn derive_from_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_row::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Embeds a directory of migrations, relative to the crate root, as a `mysql::migrate::Migrator`.
+///
+/// Files are named `<version>_<name>.up.sql`, with an optional `<version>_<name>.down.sql`
+/// to undo them. Editing a file triggers a rebuild, but adding one needs a `cargo clean` or
+/// a change to the calling crate.
+#[proc_macro]
+pub fn embed_migrations(input: TokenStream) -> TokenStream {
+    let dir = parse_macro_input!(input as LitStr);
+    migrations::expand(dir)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Runs an `async fn(db: Database)` test on a scratch database of its own, created on the
+/// server in `DATABASE_URL` and dropped afterwards even if the test panics. Needs the
+/// `test-support` feature of `mysql`.
+///
+/// * `migrations = "dir"` applies the migrations in `dir`, as with `embed_migrations!`.
+/// * `fixtures("users.yaml", "cleanup.sql")` then loads the given fixtures, see
+///   `mysql::fixtures`.
+///
+/// Paths are relative to the crate root.
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut parsed = test::Args::default();
+    let parser = syn::meta::parser(|meta| parsed.parse(meta));
+    parse_macro_input!(args with parser);
+    let input = parse_macro_input!(input as ItemFn);
+    test::expand(parsed, input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
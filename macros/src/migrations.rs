@@ -0,0 +1,98 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use syn::{Error, LitStr, Result};
+
+#[path = "../../src/migrate/file_name.rs"]
+mod file_name;
+
+use file_name::parse_file_name;
+
+#[derive(Default)]
+struct Files {
+    name: String,
+    up: Option<PathBuf>,
+    down: Option<PathBuf>,
+}
+
+pub fn expand(dir: LitStr) -> Result<TokenStream> {
+    let root = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| Error::new(dir.span(), "CARGO_MANIFEST_DIR is not set"))?;
+    let path = Path::new(&root).join(dir.value());
+    let entries = std::fs::read_dir(&path).map_err(|err| {
+        Error::new(
+            dir.span(),
+            format!("failed to read `{}`: {}", path.display(), err),
+        )
+    })?;
+
+    let mut migrations = BTreeMap::<u64, Files>::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|err| Error::new(dir.span(), err.to_string()))?
+            .path();
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if !file_name.ends_with(".sql") {
+            continue;
+        }
+        let (version, name, down) = parse_file_name(file_name).ok_or_else(|| {
+            Error::new(
+                dir.span(),
+                format!(
+                    "`{}` is not named `<version>_<name>.up.sql` or `<version>_<name>.down.sql`",
+                    file_name
+                ),
+            )
+        })?;
+        let files = migrations.entry(version).or_default();
+        if !files.name.is_empty() && files.name != name {
+            return Err(Error::new(
+                dir.span(),
+                format!("migration version {} is used more than once", version),
+            ));
+        }
+        files.name = name.to_string();
+        let slot = if down { &mut files.down } else { &mut files.up };
+        if slot.replace(path.clone()).is_some() {
+            return Err(Error::new(
+                dir.span(),
+                format!("migration version {} is used more than once", version),
+            ));
+        }
+    }
+
+    let migrations = migrations
+        .into_iter()
+        .map(|(version, files)| {
+            let up = files.up.ok_or_else(|| {
+                Error::new(
+                    dir.span(),
+                    format!("migration {} has a down script but no up script", version),
+                )
+            })?;
+            let name = &files.name;
+            let up = path_literal(&up);
+            let down = match files.down {
+                Some(down) => {
+                    let down = path_literal(&down);
+                    quote!(::std::option::Option::Some(::std::include_str!(#down)))
+                }
+                None => quote!(::std::option::Option::None::<&str>),
+            };
+            Ok(quote! {
+                ::mysql::migrate::Migration::new(#version, #name, ::std::include_str!(#up), #down)
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        ::mysql::__private::embedded_migrator(::std::vec![#(#migrations),*])
+    })
+}
+
+fn path_literal(path: &Path) -> LitStr {
+    LitStr::new(&path.to_string_lossy(), Span::call_site())
+}
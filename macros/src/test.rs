@@ -0,0 +1,113 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::path::Path;
+use syn::meta::ParseNestedMeta;
+use syn::punctuated::Punctuated;
+use syn::{parenthesized, Error, FnArg, ItemFn, LitStr, Result, Token};
+
+#[derive(Default)]
+pub struct Args {
+    migrations: Option<LitStr>,
+    fixtures: Vec<LitStr>,
+}
+
+impl Args {
+    pub fn parse(&mut self, meta: ParseNestedMeta) -> Result<()> {
+        if meta.path.is_ident("migrations") {
+            self.migrations = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("fixtures") {
+            let content;
+            parenthesized!(content in meta.input);
+            let fixtures = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+            self.fixtures.extend(fixtures);
+        } else {
+            return Err(meta.error("expected `migrations = \"...\"` or `fixtures(\"...\")`"));
+        }
+        Ok(())
+    }
+}
+
+pub fn expand(args: Args, item: ItemFn) -> Result<TokenStream> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = item;
+    if sig.asyncness.is_none() {
+        return Err(Error::new_spanned(
+            sig.fn_token,
+            "`#[mysql::test]` functions must be `async`",
+        ));
+    }
+    if sig.inputs.len() != 1 || matches!(sig.inputs.first(), Some(FnArg::Receiver(_))) {
+        return Err(Error::new_spanned(
+            &sig.inputs,
+            "`#[mysql::test]` functions take a single `Database` argument",
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &sig.generics,
+            "`#[mysql::test]` functions cannot be generic",
+        ));
+    }
+
+    let root = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| Error::new_spanned(&sig.ident, "CARGO_MANIFEST_DIR is not set"))?;
+    let migrator = args
+        .migrations
+        .map(|dir| quote!(.with_migrator(::mysql::embed_migrations!(#dir))));
+    let fixtures = args
+        .fixtures
+        .iter()
+        .map(|fixture| {
+            let path = Path::new(&root).join(fixture.value());
+            if !path.is_file() {
+                return Err(Error::new(
+                    fixture.span(),
+                    format!("fixture `{}` does not exist", path.display()),
+                ));
+            }
+            let format = match path.extension().and_then(|extension| extension.to_str()) {
+                Some("sql") => quote!(Sql),
+                Some("json") => quote!(Json),
+                Some("yaml" | "yml") => quote!(Yaml),
+                _ => {
+                    return Err(Error::new(
+                        fixture.span(),
+                        "fixtures must be `.sql`, `.json`, `.yaml` or `.yml` files",
+                    ))
+                }
+            };
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let path = LitStr::new(&path.to_string_lossy(), fixture.span());
+            Ok(quote! {
+                .with_fixture(::mysql::fixtures::Fixture::new(
+                    #name,
+                    ::mysql::fixtures::Format::#format,
+                    ::std::include_str!(#path),
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let name = &sig.ident;
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+    Ok(quote! {
+        #[::core::prelude::v1::test]
+        #(#attrs)*
+        #vis fn #name() #output {
+            async fn #name(#inputs) #output #block
+
+            ::mysql::test_support::TestSetup::new()
+                #migrator
+                #(#fixtures)*
+                .run(::std::stringify!(#name), #name)
+        }
+    })
+}
@@ -0,0 +1,172 @@
+use std::iter::Peekable;
+
+use crate::in_list::{self, ParamsError};
+use crate::{Error, Result, Value};
+
+/// The most placeholders a prepared statement can have.
+pub(crate) const MAX_PLACEHOLDERS: usize = u16::MAX as usize;
+
+/// Used when the server does not report `max_allowed_packet`.
+pub(crate) const DEFAULT_MAX_ALLOWED_PACKET: usize = 4 * 1024 * 1024;
+
+/// Room left in each packet for the `COM_STMT_EXECUTE` header and the `NULL` bitmap.
+const PACKET_OVERHEAD: usize = 1024;
+
+/// Quotes an identifier with backticks, quoting each part of a `schema.table` name separately.
+/// A `*` part, as in `users.*`, is left as is.
+pub(crate) fn quote_identifier(name: &str) -> String {
+    name.split('.')
+        .map(|part| match part {
+            "*" => part.to_string(),
+            part => format!("`{}`", part.replace('`', "``")),
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Splits rows into multi-row `INSERT` statements that each fit in a single packet.
+pub(crate) struct BulkInsert {
+    prefix: String,
+    row: String,
+    columns: usize,
+}
+
+impl BulkInsert {
+    pub(crate) fn new(table: &str, columns: &[&str]) -> Result<Self> {
+        if columns.is_empty() {
+            return Err(Error::Other("bulk insert needs at least one column".into()));
+        }
+        let names = columns
+            .iter()
+            .map(|column| quote_identifier(column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Ok(BulkInsert {
+            prefix: format!(
+                "INSERT INTO {} ({}) VALUES ",
+                quote_identifier(table),
+                names
+            ),
+            row: format!("({})", vec!["?"; columns.len()].join(", ")),
+            columns: columns.len(),
+        })
+    }
+
+    /// Yields `(query, values)` pairs. A single row larger than `max_allowed_packet` still gets
+    /// its own statement, since the driver sends oversized values separately.
+    pub(crate) fn chunks<I>(&self, rows: I, max_allowed_packet: usize) -> Chunks<'_, I::IntoIter>
+    where
+        I: IntoIterator<Item = Vec<Value>>,
+    {
+        Chunks {
+            insert: self,
+            rows: rows.into_iter().peekable(),
+            max_size: max_allowed_packet.saturating_sub(PACKET_OVERHEAD),
+        }
+    }
+}
+
+pub(crate) struct Chunks<'a, I: Iterator> {
+    insert: &'a BulkInsert,
+    rows: Peekable<I>,
+    max_size: usize,
+}
+
+impl<I: Iterator<Item = Vec<Value>>> Iterator for Chunks<'_, I> {
+    type Item = Result<(String, Vec<Value>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let columns = self.insert.columns;
+        let mut values = Vec::new();
+        let mut rows = 0;
+        let mut size = 0;
+        while let Some(row) = self.rows.peek() {
+            if row.len() != columns {
+                let message = format!(
+                    "bulk insert row has {} values, expected {}",
+                    row.len(),
+                    columns
+                );
+                return Some(Err(Error::Other(message.into())));
+            }
+            if row.iter().any(in_list::is_list) {
+                return Some(Err(ParamsError::UnexpectedList.into()));
+            }
+            // Each value is sent with two bytes for its type.
+            let row_size = row
+                .iter()
+                .map(|value| value.bin_len() as usize + 2)
+                .sum::<usize>();
+            if rows > 0
+                && (size + row_size > self.max_size || values.len() + columns > MAX_PLACEHOLDERS)
+            {
+                break;
+            }
+            size += row_size;
+            rows += 1;
+            values.extend(self.rows.next().expect("peeked row"));
+        }
+        if rows == 0 {
+            return None;
+        }
+        let query = self.insert.prefix.clone() + &vec![self.insert.row.as_str(); rows].join(", ");
+        Some(Ok((query, values)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_identifier() {
+        assert_eq!(quote_identifier("users"), "`users`");
+        assert_eq!(quote_identifier("app.users"), "`app`.`users`");
+        assert_eq!(quote_identifier("we`ird"), "`we``ird`");
+        assert_eq!(quote_identifier("users.*"), "`users`.*");
+    }
+
+    #[test]
+    fn test_chunks() {
+        let insert = BulkInsert::new("users", &["id", "name"]).unwrap();
+        let rows = (0..3).map(|id| vec![Value::from(id), Value::from("name")]);
+        let chunks = insert
+            .chunks(rows, 1 << 20)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(
+            chunks[0].0,
+            "INSERT INTO `users` (`id`, `name`) VALUES (?, ?), (?, ?), (?, ?)"
+        );
+        assert_eq!(chunks[0].1.len(), 6);
+
+        assert!(insert.chunks(Vec::new(), 1 << 20).next().is_none());
+    }
+
+    #[test]
+    fn test_chunks_split_by_size_and_placeholders() {
+        let insert = BulkInsert::new("t", &["a"]).unwrap();
+        let rows = (0..4).map(|_| vec![Value::from(vec![0u8; 400])]);
+        let sizes = insert
+            .chunks(rows, PACKET_OVERHEAD + 1000)
+            .map(|chunk| chunk.unwrap().1.len())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, [2, 2]);
+
+        let rows = (0..MAX_PLACEHOLDERS + 1).map(|id| vec![Value::from(id)]);
+        let sizes = insert
+            .chunks(rows, usize::MAX)
+            .map(|chunk| chunk.unwrap().1.len())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, [MAX_PLACEHOLDERS, 1]);
+    }
+
+    #[test]
+    fn test_row_length_mismatch() {
+        let insert = BulkInsert::new("t", &["a", "b"]).unwrap();
+        let mut chunks = insert.chunks(vec![vec![Value::from(1)]], 1 << 20);
+        assert!(matches!(chunks.next(), Some(Err(Error::Other(_)))));
+        assert!(BulkInsert::new("t", &[]).is_err());
+    }
+}
@@ -0,0 +1,329 @@
+//! Applies and manages the migrations in a directory against the database in `DATABASE_URL`,
+//! and seeds it with fixtures.
+
+use mysql::fixtures::Fixtures;
+use mysql::migrate::{Migration, MigrationState, MigrationStatus, Migrator};
+use mysql::Database;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const USAGE: &str = "\
+Usage: mysql-migrate [--source <dir>] [--fixtures <dir>] [--dry-run] <command>
+
+Commands:
+  new <name>          Create an empty up and down migration
+  up                  Apply all pending migrations
+  down                Undo the latest applied migration
+  status              List migrations and whether they are applied
+  redo                Undo and reapply the latest applied migration
+  baseline <version>  Mark migrations up to <version> as applied without running them
+  seed [<name>...]    Load all fixtures, or only the named ones
+
+Options:
+  --source <dir>    Directory containing the migrations [default: migrations]
+  --fixtures <dir>  Directory containing the fixtures [default: fixtures]
+  --dry-run         Print the SQL that would run instead of running it
+
+DATABASE_URL is read from the environment or a .env file.";
+
+enum Command {
+    New(String),
+    Up,
+    Down,
+    Status,
+    Redo,
+    Baseline(u64),
+    Seed(Vec<String>),
+}
+
+struct Cli {
+    source: PathBuf,
+    fixtures: PathBuf,
+    dry_run: bool,
+    command: Command,
+}
+
+impl Cli {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut source = PathBuf::from("migrations");
+        let mut fixtures = PathBuf::from("fixtures");
+        let mut dry_run = false;
+        let mut positional = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--source" => source = args.next().ok_or("`--source` needs a directory")?.into(),
+                "--fixtures" => {
+                    fixtures = args.next().ok_or("`--fixtures` needs a directory")?.into()
+                }
+                "--dry-run" => dry_run = true,
+                "-h" | "--help" => return Err(String::new()),
+                flag if flag.starts_with('-') => return Err(format!("unknown option `{}`", flag)),
+                _ => positional.push(arg),
+            }
+        }
+        let mut positional = positional.into_iter();
+        let command = match positional.next().as_deref() {
+            Some("new") => Command::New(positional.next().ok_or("`new` needs a name")?),
+            Some("up") => Command::Up,
+            Some("down") => Command::Down,
+            Some("status") => Command::Status,
+            Some("redo") => Command::Redo,
+            Some("baseline") => {
+                let version = positional.next().ok_or("`baseline` needs a version")?;
+                Command::Baseline(
+                    version
+                        .parse()
+                        .map_err(|_| format!("invalid version `{}`", version))?,
+                )
+            }
+            Some("seed") => Command::Seed(positional.by_ref().collect()),
+            Some(command) => return Err(format!("unknown command `{}`", command)),
+            None => return Err("missing command".to_string()),
+        };
+        if let Some(arg) = positional.next() {
+            return Err(format!("unexpected argument `{}`", arg));
+        }
+        Ok(Cli {
+            source,
+            fixtures,
+            dry_run,
+            command,
+        })
+    }
+
+    async fn run(self) -> Result<(), Box<dyn Error>> {
+        match &self.command {
+            Command::New(name) => return new(&self.source, name),
+            Command::Seed(names) => return self.seed(names).await,
+            _ => {}
+        }
+        let migrator = Migrator::from_dir(&self.source)?;
+        let database = Database::from_env()?;
+        let statuses = migrator.status(&database).await?;
+        let pending = statuses
+            .iter()
+            .filter(|status| status.state == MigrationState::Pending);
+        let latest = statuses
+            .iter()
+            .rfind(|status| status.state == MigrationState::Applied);
+        match self.command {
+            Command::New(_) | Command::Seed(_) => unreachable!(),
+            Command::Status => {
+                for status in &statuses {
+                    println!(
+                        "{:>14}  {:<7}  {}",
+                        status.version,
+                        state(status.state),
+                        status.name
+                    );
+                }
+            }
+            Command::Up if self.dry_run => {
+                for status in pending {
+                    print_sql(status, &find(&migrator, status.version).up);
+                }
+            }
+            Command::Up => report("applied", &migrator.run(&database).await?),
+            Command::Baseline(version) if self.dry_run => {
+                for status in pending.filter(|status| status.version <= version) {
+                    println!(
+                        "-- {} {} would be marked as applied",
+                        status.version, status.name
+                    );
+                }
+            }
+            Command::Baseline(version) => {
+                report("marked", &migrator.baseline(&database, version).await?)
+            }
+            Command::Down | Command::Redo => {
+                let latest = latest.ok_or("no migrations have been applied")?;
+                let migration = find(&migrator, latest.version);
+                let down = migration
+                    .down
+                    .as_deref()
+                    .ok_or_else(|| format!("migration {} has no down script", latest.version))?;
+                if self.dry_run {
+                    print_sql(latest, down);
+                    if let Command::Redo = self.command {
+                        print_sql(latest, &migration.up);
+                    }
+                    return Ok(());
+                }
+                // Undo down to the version before the latest applied one.
+                let target = statuses
+                    .iter()
+                    .rfind(|status| status.version < latest.version)
+                    .map_or(0, |status| status.version);
+                report("undone", &migrator.undo(&database, target).await?);
+                if let Command::Redo = self.command {
+                    report("applied", &migrator.run(&database).await?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn seed(&self, names: &[String]) -> Result<(), Box<dyn Error>> {
+        let mut fixtures = Fixtures::from_dir(&self.fixtures)?;
+        if !names.is_empty() {
+            fixtures = fixtures.select(&names.iter().map(String::as_str).collect::<Vec<_>>())?;
+        }
+        if self.dry_run {
+            for fixture in fixtures.fixtures() {
+                println!("-- {} would be loaded", fixture.name);
+            }
+            return Ok(());
+        }
+        fixtures.load(&Database::from_env()?).await?;
+        for fixture in fixtures.fixtures() {
+            println!("loaded {}", fixture.name);
+        }
+        Ok(())
+    }
+}
+
+fn state(state: MigrationState) -> &'static str {
+    match state {
+        MigrationState::Pending => "pending",
+        MigrationState::Applied => "applied",
+        MigrationState::Changed => "changed",
+        MigrationState::Failed => "failed",
+        MigrationState::Missing => "missing",
+    }
+}
+
+fn find(migrator: &Migrator, version: u64) -> &Migration {
+    migrator
+        .migrations()
+        .iter()
+        .find(|migration| migration.version == version)
+        .expect("status lists known migrations")
+}
+
+fn print_sql(status: &MigrationStatus, sql: &str) {
+    println!("-- {} {}\n{}", status.version, status.name, sql.trim_end());
+}
+
+fn report(action: &str, versions: &[u64]) {
+    if versions.is_empty() {
+        println!("nothing to do");
+    }
+    for version in versions {
+        println!("{} {}", action, version);
+    }
+}
+
+fn new(source: &Path, name: &str) -> Result<(), Box<dyn Error>> {
+    if name.is_empty() || !name.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_') {
+        return Err(format!(
+            "migration names may only use letters, digits and `_`, got `{}`",
+            name
+        )
+        .into());
+    }
+    std::fs::create_dir_all(source)?;
+    let version = timestamp(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs());
+    for direction in ["up", "down"] {
+        let path = source.join(format!("{}_{}.{}.sql", version, name, direction));
+        std::fs::File::create_new(&path)?;
+        println!("created {}", path.display());
+    }
+    Ok(())
+}
+
+/// Formats seconds since the epoch as a `YYYYMMDDHHMMSS` version in UTC.
+fn timestamp(secs: u64) -> u64 {
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // Converts days since 1970-01-01 to a civil date, after Howard Hinnant's `civil_from_days`.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    let time = secs / 3600 * 10_000 + secs % 3600 / 60 * 100 + secs % 60;
+    (year * 10_000 + month * 100 + day) * 1_000_000 + time
+}
+
+fn main() -> ExitCode {
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(message) if message.is_empty() => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    match runtime.block_on(cli.run()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Cli, String> {
+        Cli::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse() {
+        let cli = parse("--dry-run up --source db/migrations").unwrap();
+        assert!(cli.dry_run);
+        assert_eq!(cli.source, Path::new("db/migrations"));
+        assert!(matches!(cli.command, Command::Up));
+
+        assert!(matches!(
+            parse("baseline 20240101000000").unwrap().command,
+            Command::Baseline(20240101000000)
+        ));
+        assert!(
+            matches!(parse("new add_users").unwrap().command, Command::New(name) if name == "add_users")
+        );
+        let cli = parse("--fixtures dev seed users posts").unwrap();
+        assert_eq!(cli.fixtures, Path::new("dev"));
+        assert!(matches!(cli.command, Command::Seed(names) if names == ["users", "posts"]));
+        assert!(matches!(parse("seed").unwrap().command, Command::Seed(names) if names.is_empty()));
+        assert!(parse("baseline latest").is_err());
+        assert!(parse("up extra").is_err());
+        assert!(parse("--force up").is_err());
+        assert!(parse("").is_err());
+        assert_eq!(parse("--help").err().as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(0), 19700101000000);
+        assert_eq!(timestamp(951_782_400 + 3_723), 20000229010203);
+        assert_eq!(timestamp(1_735_689_599), 20241231235959);
+    }
+}
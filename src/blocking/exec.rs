@@ -0,0 +1,147 @@
+use mysql_sync::{prelude::*, Conn, LocalInfileHandler};
+use serde::de::DeserializeOwned;
+use std::io::{self, Read};
+use std::iter;
+
+use crate::batch::{BulkInsert, DEFAULT_MAX_ALLOWED_PACKET};
+use crate::infile::{self, LoadDataOptions};
+use crate::{de, in_list, trace, ExecResult, Params, Result, Row, Value};
+
+pub(super) fn select<T: FromRow>(
+    conn: &mut Conn,
+    query: &str,
+    params_map: Option<Params>,
+    max_list_len: Option<usize>,
+) -> Result<Vec<T>> {
+    let (query, params) = in_list::expand(query, params_map, max_list_len)?;
+    trace::statement(&query, conn.connection_id());
+    trace::parameters(&params);
+    let rows = conn.exec_map(query.as_ref(), params, |row: Row| T::from_row_opt(row))?;
+    trace::returned_rows(rows.len());
+    Ok(rows.into_iter().collect::<Result<_, _>>()?)
+}
+
+pub(super) fn select_as<T: DeserializeOwned>(
+    conn: &mut Conn,
+    query: &str,
+    params_map: Option<Params>,
+    max_list_len: Option<usize>,
+) -> Result<Vec<T>> {
+    let (query, params) = in_list::expand(query, params_map, max_list_len)?;
+    trace::statement(&query, conn.connection_id());
+    trace::parameters(&params);
+    let rows = conn.exec_map(query.as_ref(), params, |row: Row| de::from_row(row))?;
+    trace::returned_rows(rows.len());
+    Ok(rows.into_iter().collect::<Result<_, _>>()?)
+}
+
+pub(super) fn execute(
+    conn: &mut Conn,
+    query: &str,
+    params_map: Option<Params>,
+    max_list_len: Option<usize>,
+) -> Result<ExecResult> {
+    let (query, params) = in_list::expand(query, params_map, max_list_len)?;
+    trace::statement(&query, conn.connection_id());
+    trace::parameters(&params);
+    conn.exec_drop(query.as_ref(), params)?;
+    trace::affected_rows(conn.affected_rows());
+    Ok(ExecResult {
+        affected_rows: conn.affected_rows(),
+        last_insert_id: Some(conn.last_insert_id()).filter(|id| *id != 0),
+        warnings: conn.warnings(),
+        info: conn.info_str().into_owned(),
+    })
+}
+
+pub(super) fn execute_batch<I>(
+    conn: &mut Conn,
+    query: &str,
+    params: I,
+    max_list_len: Option<usize>,
+) -> Result<u64>
+where
+    I: IntoIterator<Item = Params>,
+{
+    let mut params = params
+        .into_iter()
+        .map(|params| in_list::expand(query, Some(params), max_list_len));
+    let Some(first) = params.next() else {
+        return Ok(0);
+    };
+    let (prepared, first) = first?;
+    trace::statement(&prepared, conn.connection_id());
+    let statement = conn.prep(prepared.as_ref())?;
+    let mut affected_rows = 0;
+    for expanded in iter::once(Ok((prepared.clone(), first))).chain(params) {
+        let (query, params) = expanded?;
+        in_list::check_batch(&prepared, &query)?;
+        trace::parameters(&params);
+        conn.exec_drop(&statement, params)?;
+        affected_rows += conn.affected_rows();
+    }
+    trace::affected_rows(affected_rows);
+    Ok(affected_rows)
+}
+
+pub(super) fn bulk_insert<I>(conn: &mut Conn, table: &str, columns: &[&str], rows: I) -> Result<u64>
+where
+    I: IntoIterator<Item = Vec<Value>>,
+{
+    let insert = BulkInsert::new(table, columns)?;
+    let max_allowed_packet = conn
+        .query_first("SELECT @@max_allowed_packet")?
+        .unwrap_or(DEFAULT_MAX_ALLOWED_PACKET);
+    let mut affected_rows = 0;
+    for chunk in insert.chunks(rows, max_allowed_packet) {
+        let (query, values) = chunk?;
+        trace::statement(&query, conn.connection_id());
+        trace::values(&values);
+        conn.exec_drop(query, values)?;
+        affected_rows += conn.affected_rows();
+    }
+    trace::affected_rows(affected_rows);
+    Ok(affected_rows)
+}
+
+pub(super) fn reader_handler<R: Read + Send + 'static>(reader: R) -> LocalInfileHandler {
+    let mut reader = Some(reader);
+    LocalInfileHandler::new(move |_, infile| match reader.take() {
+        Some(mut reader) => io::copy(&mut reader, infile).map(|_| ()),
+        None => Ok(()),
+    })
+}
+
+pub(super) fn rows_handler<I>(rows: I, options: LoadDataOptions) -> LocalInfileHandler
+where
+    I: Iterator<Item = Vec<Value>> + Send + 'static,
+{
+    let mut rows = Some(rows);
+    LocalInfileHandler::new(move |_, infile| match rows.take() {
+        Some(rows) => infile::write_rows(infile, rows, &options),
+        None => Ok(()),
+    })
+}
+
+pub(super) fn load_data(
+    conn: &mut Conn,
+    table: &str,
+    columns: &[&str],
+    handler: LocalInfileHandler,
+    options: &LoadDataOptions,
+) -> Result<ExecResult> {
+    // The handler only ever serves this data, whatever file name the server asks for.
+    let statement = options.statement("data", table, columns);
+    trace::statement(&statement, conn.connection_id());
+    conn.set_local_infile_handler(Some(handler));
+    let result = conn.query_drop(statement);
+    conn.set_local_infile_handler(None);
+    result?;
+    trace::affected_rows(conn.affected_rows());
+    Ok(ExecResult {
+        affected_rows: conn.affected_rows(),
+        last_insert_id: None,
+        warnings: conn.warnings(),
+        info: conn.info_str().into_owned(),
+    })
+}
@@ -0,0 +1,117 @@
+//! A synchronous API backed by an r2d2 connection pool, for code that does not run on an
+//! async runtime. Enabled with the `blocking` feature.
+//!
+//! It mirrors the async API: the same URL parameters, row conversions, errors and
+//! transaction retries, with every method blocking the calling thread instead.
+//!
+//! With `default-features = false, features = ["blocking"]` neither mysql_async nor tokio is
+//! compiled, and the async API is left out.
+
+use serde::de::DeserializeOwned;
+use std::io::Read;
+use std::sync::OnceLock;
+
+mod database;
+mod exec;
+mod transaction;
+
+pub use database::{ConnectionManager, Database};
+pub use transaction::Transaction;
+
+use crate::{
+    ConfigError, ExecResult, FromRow, FromValue, LoadDataOptions, Params, Result, TxOptions, Value,
+};
+
+static DEFAULT: OnceLock<Database> = OnceLock::new();
+
+/// Returns the default blocking database, building it from `DATABASE_URL` on first use.
+///
+/// No connection is opened, so this only reports configuration errors.
+pub fn try_init() -> Result<&'static Database, ConfigError> {
+    if let Some(database) = DEFAULT.get() {
+        return Ok(database);
+    }
+    let database = Database::from_env()?;
+    Ok(DEFAULT.get_or_init(|| database))
+}
+
+/// Eagerly initializes the default blocking database and checks that the server is reachable.
+pub fn init() -> Result<&'static Database> {
+    let database = try_init()?;
+    database.ping()?;
+    Ok(database)
+}
+
+pub fn select<T: FromRow>(query: &str, params_map: Option<Params>) -> Result<Vec<T>> {
+    try_init()?.select(query, params_map)
+}
+
+pub fn select_as<T: DeserializeOwned>(query: &str, params_map: Option<Params>) -> Result<Vec<T>> {
+    try_init()?.select_as(query, params_map)
+}
+
+pub fn select_one<T: FromRow>(query: &str, params_map: Option<Params>) -> Result<T> {
+    try_init()?.select_one(query, params_map)
+}
+
+pub fn select_optional<T: FromRow>(query: &str, params_map: Option<Params>) -> Result<Option<T>> {
+    try_init()?.select_optional(query, params_map)
+}
+
+pub fn select_scalar<T: FromValue>(query: &str, params_map: Option<Params>) -> Result<T> {
+    try_init()?.select_scalar(query, params_map)
+}
+
+pub fn execute(query: &str, params_map: Option<Params>) -> Result<ExecResult> {
+    try_init()?.execute(query, params_map)
+}
+
+pub fn execute_batch<I>(query: &str, params: I) -> Result<u64>
+where
+    I: IntoIterator<Item = Params>,
+{
+    try_init()?.execute_batch(query, params)
+}
+
+pub fn bulk_insert<I>(table: &str, columns: &[&str], rows: I) -> Result<u64>
+where
+    I: IntoIterator<Item = Vec<Value>>,
+{
+    try_init()?.bulk_insert(table, columns, rows)
+}
+
+pub fn load_data<R>(
+    table: &str,
+    columns: &[&str],
+    reader: R,
+    options: LoadDataOptions,
+) -> Result<ExecResult>
+where
+    R: Read + Send + 'static,
+{
+    try_init()?.load_data(table, columns, reader, options)
+}
+
+pub fn load_rows<I>(
+    table: &str,
+    columns: &[&str],
+    rows: I,
+    options: LoadDataOptions,
+) -> Result<ExecResult>
+where
+    I: IntoIterator<Item = Vec<Value>>,
+    I::IntoIter: Send + 'static,
+{
+    try_init()?.load_rows(table, columns, rows, options)
+}
+
+pub fn begin(options: TxOptions) -> Result<Transaction> {
+    try_init()?.begin(options)
+}
+
+pub fn transaction<T, F>(options: TxOptions, f: F) -> Result<T>
+where
+    F: FnMut(&mut Transaction) -> Result<T>,
+{
+    try_init()?.transaction(options, f)
+}
@@ -0,0 +1,165 @@
+use mysql_sync::{prelude::*, Conn};
+use r2d2::PooledConnection;
+use serde::de::DeserializeOwned;
+use std::io::Read;
+
+use super::{exec, ConnectionManager};
+use crate::{trace, ExecResult, LoadDataOptions, Params, Result, TxOptions, Value};
+
+/// A blocking transaction holding a single pooled connection.
+///
+/// The transaction is rolled back if it is dropped without calling [`Transaction::commit`].
+pub struct Transaction {
+    conn: Option<PooledConnection<ConnectionManager>>,
+    max_list_len: Option<usize>,
+}
+
+impl Transaction {
+    pub(super) fn begin(
+        mut conn: PooledConnection<ConnectionManager>,
+        options: &TxOptions,
+        max_list_len: Option<usize>,
+    ) -> Result<Self> {
+        for statement in options.begin_statements() {
+            conn.query_drop(statement)?;
+        }
+        Ok(Transaction {
+            conn: Some(conn),
+            max_list_len,
+        })
+    }
+
+    pub fn select<T: FromRow>(
+        &mut self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<Vec<T>> {
+        let max_list_len = self.max_list_len;
+        trace::Query::new(query)
+            .run_blocking(|| exec::select(self.conn(), query, params_map, max_list_len))
+    }
+
+    /// Like [`Transaction::select`], but deserializes each row with serde instead of `FromRow`.
+    pub fn select_as<T: DeserializeOwned>(
+        &mut self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<Vec<T>> {
+        let max_list_len = self.max_list_len;
+        trace::Query::new(query)
+            .run_blocking(|| exec::select_as(self.conn(), query, params_map, max_list_len))
+    }
+
+    /// Returns the only row of the result, failing with [`Error::NotFound`](crate::Error::NotFound)
+    /// or [`Error::TooManyRows`](crate::Error::TooManyRows) otherwise.
+    pub fn select_one<T: FromRow>(&mut self, query: &str, params_map: Option<Params>) -> Result<T> {
+        crate::result::one(self.select(query, params_map)?)
+    }
+
+    /// Returns the only row of the result if there is one, failing with
+    /// [`Error::TooManyRows`](crate::Error::TooManyRows) if there are several.
+    pub fn select_optional<T: FromRow>(
+        &mut self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<Option<T>> {
+        crate::result::optional(self.select(query, params_map)?)
+    }
+
+    /// Returns the first column of the only row, e.g. for `SELECT COUNT(*)`.
+    pub fn select_scalar<T: FromValue>(
+        &mut self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<T> {
+        let (value,) = self.select_one::<(T,)>(query, params_map)?;
+        Ok(value)
+    }
+
+    pub fn execute(&mut self, query: &str, params_map: Option<Params>) -> Result<ExecResult> {
+        let max_list_len = self.max_list_len;
+        trace::Query::new(query)
+            .run_blocking(|| exec::execute(self.conn(), query, params_map, max_list_len))
+    }
+
+    /// Executes `query` once for every set of parameters, preparing it only once. Returns the
+    /// total number of affected rows.
+    pub fn execute_batch<I>(&mut self, query: &str, params: I) -> Result<u64>
+    where
+        I: IntoIterator<Item = Params>,
+    {
+        let max_list_len = self.max_list_len;
+        trace::Query::new(query)
+            .run_blocking(|| exec::execute_batch(self.conn(), query, params, max_list_len))
+    }
+
+    /// Inserts `rows` into `table` with multi-row `INSERT` statements that each fit in
+    /// `max_allowed_packet`. Returns the total number of affected rows.
+    pub fn bulk_insert<I>(&mut self, table: &str, columns: &[&str], rows: I) -> Result<u64>
+    where
+        I: IntoIterator<Item = Vec<Value>>,
+    {
+        trace::Query::new("INSERT")
+            .run_blocking(|| exec::bulk_insert(self.conn(), table, columns, rows))
+    }
+
+    /// Loads `reader` into `table` with `LOAD DATA LOCAL INFILE`, the fastest way to import
+    /// data. The data must be in the format described by `options`, and `columns` lists the
+    /// columns of each line, or is empty to load every column of the table in order.
+    pub fn load_data<R>(
+        &mut self,
+        table: &str,
+        columns: &[&str],
+        reader: R,
+        options: LoadDataOptions,
+    ) -> Result<ExecResult>
+    where
+        R: Read + Send + 'static,
+    {
+        let handler = exec::reader_handler(reader);
+        trace::Query::new("LOAD DATA")
+            .run_blocking(|| exec::load_data(self.conn(), table, columns, handler, &options))
+    }
+
+    /// Like [`Transaction::load_data`], but encodes `rows` in the format described by `options`.
+    pub fn load_rows<I>(
+        &mut self,
+        table: &str,
+        columns: &[&str],
+        rows: I,
+        options: LoadDataOptions,
+    ) -> Result<ExecResult>
+    where
+        I: IntoIterator<Item = Vec<Value>>,
+        I::IntoIter: Send + 'static,
+    {
+        let handler = exec::rows_handler(rows.into_iter(), options.clone());
+        trace::Query::new("LOAD DATA")
+            .run_blocking(|| exec::load_data(self.conn(), table, columns, handler, &options))
+    }
+
+    pub fn commit(mut self) -> Result<()> {
+        // If `COMMIT` fails the connection is still taken, and dropping `self` rolls back.
+        self.conn().query_drop("COMMIT")?;
+        self.conn = None;
+        Ok(())
+    }
+
+    pub fn rollback(mut self) -> Result<()> {
+        let mut conn = self.conn.take().expect("transaction is open");
+        conn.query_drop("ROLLBACK")?;
+        Ok(())
+    }
+
+    fn conn(&mut self) -> &mut Conn {
+        self.conn.as_mut().expect("transaction is open")
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if let Some(mut conn) = self.conn.take() {
+            let _ = conn.query_drop("ROLLBACK");
+        }
+    }
+}
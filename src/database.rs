@@ -0,0 +1,332 @@
+use async_stream::try_stream;
+use futures::future::BoxFuture;
+use futures::{AsyncRead, Stream};
+use mysql_async::{prelude::*, Conn, Opts, OptsBuilder, Pool};
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::config::{self, ConfigError};
+use crate::exec;
+use crate::infile::{self, LoadDataOptions};
+use crate::result::{self, ExecResult};
+use crate::{in_list, rt, trace, Params, Result, Transaction, TxOptions, Value};
+
+/// A handle to a MySQL connection pool.
+///
+/// Cloning a `Database` is cheap and every clone shares the same pool. The pool is
+/// synchronized internally, so concurrent checkouts do not wait on each other.
+#[derive(Clone)]
+pub struct Database {
+    pool: Pool,
+    timeout: Option<Duration>,
+    max_list_len: Option<usize>,
+}
+
+impl Database {
+    pub fn new<O: Into<Opts>>(opts: O) -> Self {
+        let opts = opts.into();
+        let handler = infile::Handler::new(opts.local_infile_handler());
+        Database {
+            pool: Pool::new(OptsBuilder::from_opts(opts).local_infile_handler(Some(handler))),
+            timeout: None,
+            max_list_len: None,
+        }
+    }
+
+    pub fn from_url(url: &str) -> Result<Self, ConfigError> {
+        Ok(Database::new(config::opts_from_url(url)?))
+    }
+
+    /// Builds a handle from the `DATABASE_URL` environment variable, reading `.env` if present.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Database::from_url(&config::database_url()?)
+    }
+
+    /// Like [`Database::from_url`], but also checks that a connection can be established.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let database = Database::from_url(url)?;
+        database.ping().await?;
+        Ok(database)
+    }
+
+    /// Fails every operation that takes longer than `timeout` with [`Error::Timeout`](crate::Error::Timeout),
+    /// including the wait for a pooled connection.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fails queries binding an [`InList`](crate::InList) of more than `max` values with
+    /// [`ParamsError::ListTooLong`](crate::ParamsError::ListTooLong), e.g. to bound lists that
+    /// come from request input.
+    pub fn with_max_list_len(mut self, max: usize) -> Self {
+        self.max_list_len = Some(max);
+        self
+    }
+
+    pub async fn ping(&self) -> Result<()> {
+        self.run(async {
+            let mut conn = self.pool.get_conn().await?;
+            conn.ping().await?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn select<T: FromRow + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<Vec<T>> {
+        trace::Query::new(query)
+            .run(self.run(async {
+                let mut conn = checkout(&self.pool).await?;
+                exec::select(&mut conn, query, params_map, self.max_list_len).await
+            }))
+            .await
+    }
+
+    /// Like [`Database::select`], but deserializes each row with serde instead of `FromRow`.
+    ///
+    /// Columns are matched to fields by name, and JSON columns can be deserialized into nested
+    /// structs, maps or sequences.
+    pub async fn select_as<T: DeserializeOwned + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<Vec<T>> {
+        trace::Query::new(query)
+            .run(self.run(async {
+                let mut conn = checkout(&self.pool).await?;
+                exec::select_as(&mut conn, query, params_map, self.max_list_len).await
+            }))
+            .await
+    }
+
+    /// Streams the rows of a query as they arrive from the server instead of collecting them.
+    ///
+    /// The stream holds a pooled connection until it is exhausted or dropped. Dropping it
+    /// early discards the rest of the result set before the connection is reused.
+    pub fn select_stream<T: FromRow + Send + 'static>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Stream<Item = Result<T>> + Send + 'static {
+        let pool = self.pool.clone();
+        let timeout = self.timeout;
+        let query = in_list::expand(query, params_map, self.max_list_len)
+            .map(|(query, params)| (query.into_owned(), params));
+        try_stream! {
+            let (query, params) = query?;
+            // The span covers sending the query, not consuming the rows.
+            let mut conn = None;
+            let mut result = trace::Query::new(&query)
+                .run(exec::with_timeout(timeout, async {
+                    let conn = conn.insert(checkout(&pool).await?);
+                    trace::statement(&query, conn.id());
+                    trace::server(conn.opts());
+                    trace::parameters(&params);
+                    Ok(conn.exec_iter(query.as_str(), params).await?)
+                }))
+                .await?;
+            while let Some(row) = rt::compat(result.next()).await? {
+                yield T::from_row_opt(row)?;
+            }
+        }
+    }
+
+    /// Returns the only row of the result, failing with [`Error::NotFound`](crate::Error::NotFound)
+    /// or [`Error::TooManyRows`](crate::Error::TooManyRows) otherwise.
+    pub async fn select_one<T: FromRow + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<T> {
+        result::one(self.select(query, params_map).await?)
+    }
+
+    /// Returns the only row of the result if there is one, failing with
+    /// [`Error::TooManyRows`](crate::Error::TooManyRows) if there are several.
+    pub async fn select_optional<T: FromRow + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<Option<T>> {
+        result::optional(self.select(query, params_map).await?)
+    }
+
+    /// Returns the first column of the only row, e.g. for `SELECT COUNT(*)`.
+    pub async fn select_scalar<T: FromValue + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<T> {
+        let (value,) = self.select_one::<(T,)>(query, params_map).await?;
+        Ok(value)
+    }
+
+    pub async fn execute(&self, query: &str, params_map: Option<Params>) -> Result<ExecResult> {
+        trace::Query::new(query)
+            .run(self.run(async {
+                let mut conn = checkout(&self.pool).await?;
+                exec::execute(&mut conn, query, params_map, self.max_list_len).await
+            }))
+            .await
+    }
+
+    /// Executes `query` once for every set of parameters, preparing it only once and using a
+    /// single connection. Returns the total number of affected rows.
+    ///
+    /// The executions are not atomic unless they run inside a transaction.
+    pub async fn execute_batch<I>(&self, query: &str, params: I) -> Result<u64>
+    where
+        I: IntoIterator<Item = Params> + Send,
+        I::IntoIter: Send,
+    {
+        trace::Query::new(query)
+            .run(self.run(async {
+                let mut conn = checkout(&self.pool).await?;
+                exec::execute_batch(&mut conn, query, params, self.max_list_len).await
+            }))
+            .await
+    }
+
+    /// Inserts `rows` into `table` with multi-row `INSERT` statements on a single connection,
+    /// splitting them so that each statement fits in `max_allowed_packet`. Returns the total
+    /// number of affected rows.
+    ///
+    /// A failure leaves the chunks inserted before it in place; use
+    /// [`Transaction::bulk_insert`] to insert all rows or none.
+    pub async fn bulk_insert<I>(&self, table: &str, columns: &[&str], rows: I) -> Result<u64>
+    where
+        I: IntoIterator<Item = Vec<Value>> + Send,
+        I::IntoIter: Send,
+    {
+        trace::Query::new("INSERT")
+            .run(self.run(async {
+                let mut conn = checkout(&self.pool).await?;
+                exec::bulk_insert(&mut conn, table, columns, rows).await
+            }))
+            .await
+    }
+
+    /// Loads `reader` into `table` with `LOAD DATA LOCAL INFILE`, the fastest way to import
+    /// data. The data must be in the format described by `options`, and `columns` lists the
+    /// columns of each line, or is empty to load every column of the table in order.
+    ///
+    /// The server must allow it with `local_infile=ON`.
+    pub async fn load_data<R>(
+        &self,
+        table: &str,
+        columns: &[&str],
+        reader: R,
+        options: LoadDataOptions,
+    ) -> Result<ExecResult>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        trace::Query::new("LOAD DATA")
+            .run(self.run(async {
+                let mut conn = checkout(&self.pool).await?;
+                let data = infile::from_reader(reader);
+                exec::load_data(&mut conn, table, columns, data, &options).await
+            }))
+            .await
+    }
+
+    /// Like [`Database::load_data`], but encodes `rows` in the format described by `options`.
+    pub async fn load_rows<I>(
+        &self,
+        table: &str,
+        columns: &[&str],
+        rows: I,
+        options: LoadDataOptions,
+    ) -> Result<ExecResult>
+    where
+        I: IntoIterator<Item = Vec<Value>>,
+        I::IntoIter: Send + 'static,
+    {
+        let data = infile::from_rows(rows, options.clone());
+        trace::Query::new("LOAD DATA")
+            .run(self.run(async {
+                let mut conn = checkout(&self.pool).await?;
+                exec::load_data(&mut conn, table, columns, data, &options).await
+            }))
+            .await
+    }
+
+    pub async fn begin(&self, options: TxOptions) -> Result<Transaction> {
+        let tx = self
+            .run(async { Ok(self.pool.start_transaction(options.tx_opts()).await?) })
+            .await?;
+        Ok(Transaction::new(tx, self.timeout, self.max_list_len))
+    }
+
+    /// Runs `f` inside a transaction, committing if it returns `Ok` and rolling back otherwise.
+    ///
+    /// If the transaction fails with a deadlock or lock wait timeout it is rolled back and
+    /// `f` is called again, up to [`TxOptions::with_retries`] times.
+    ///
+    /// ```ignore
+    /// let id = db
+    ///     .transaction(TxOptions::default(), |tx| {
+    ///         Box::pin(async move {
+    ///             let result = tx.execute("INSERT INTO users (name) VALUES ('a')", None).await?;
+    ///             Ok(result.last_insert_id)
+    ///         })
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn transaction<T, F>(&self, options: TxOptions, mut f: F) -> Result<T>
+    where
+        F: for<'t> FnMut(&'t Transaction) -> BoxFuture<'t, Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let tx = self.begin(options.clone()).await?;
+            let result = match f(&tx).await {
+                Ok(value) => tx.commit().await.map(|_| value),
+                Err(err) => {
+                    // The original error is more useful than a failure to roll back.
+                    let _ = tx.rollback().await;
+                    Err(err)
+                }
+            };
+            match result {
+                Err(err)
+                    if attempt < options.retries
+                        && (err.is_deadlock() || err.is_lock_wait_timeout()) =>
+                {
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Closes every connection once it is returned to the pool.
+    ///
+    /// Other clones of this handle will fail to check out new connections afterwards.
+    pub async fn disconnect(self) -> Result<()> {
+        rt::compat(self.pool.disconnect()).await?;
+        Ok(())
+    }
+
+    /// Checks out a connection for operations that need a session of their own.
+    pub(crate) async fn get_conn(&self) -> Result<Conn> {
+        self.run(checkout(&self.pool)).await
+    }
+
+    async fn run<T>(&self, operation: impl Future<Output = Result<T>>) -> Result<T> {
+        exec::with_timeout(self.timeout, operation).await
+    }
+}
+
+/// Checks out a pooled connection, recording the wait on the current query span.
+async fn checkout(pool: &Pool) -> Result<Conn> {
+    let start = Instant::now();
+    let conn = pool.get_conn().await?;
+    trace::pool_wait(start.elapsed());
+    Ok(conn)
+}
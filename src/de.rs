@@ -0,0 +1,511 @@
+use mysql_common::constants::ColumnType;
+use mysql_common::packets::Column;
+use serde::de::value::{Error, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::{Row, Value};
+
+/// Deserializes a row into `T`, matching columns to fields by name.
+///
+/// Columns holding JSON are deserialized into nested structs, maps and sequences.
+pub fn from_row<T: DeserializeOwned>(row: Row) -> Result<T, Error> {
+    T::deserialize(RowDeserializer { row })
+}
+
+struct RowDeserializer {
+    row: Row,
+}
+
+impl<'de> de::Deserializer<'de> for RowDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let columns = self.row.columns();
+        let values = self.row.unwrap_raw();
+        visitor.visit_map(RowAccess {
+            columns: columns.iter().zip(values).collect::<Vec<_>>().into_iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let columns = self.row.columns();
+        let values = self.row.unwrap_raw();
+        let values = columns
+            .iter()
+            .zip(values)
+            .map(|(column, value)| ValueDeserializer::new(column, value.unwrap_or(Value::NULL)));
+        visitor.visit_seq(SeqDeserializer::new(values))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct enum identifier ignored_any
+    }
+}
+
+struct RowAccess<'a, I: Iterator<Item = (&'a Column, Option<Value>)>> {
+    columns: I,
+    value: Option<(&'a Column, Value)>,
+}
+
+impl<'de, 'a, I: Iterator<Item = (&'a Column, Option<Value>)>> MapAccess<'de> for RowAccess<'a, I> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.columns.next() {
+            Some((column, value)) => {
+                self.value = Some((column, value.unwrap_or(Value::NULL)));
+                let name = column.name_str().into_owned();
+                seed.deserialize(name.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (column, value) = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value requested before key"))?;
+        seed.deserialize(ValueDeserializer::new(column, value))
+            .map_err(|err| {
+                de::Error::custom(format_args!("column `{}`: {}", column.name_str(), err))
+            })
+    }
+}
+
+struct ValueDeserializer {
+    column_type: ColumnType,
+    value: Value,
+}
+
+impl ValueDeserializer {
+    fn new(column: &Column, value: Value) -> Self {
+        ValueDeserializer {
+            column_type: column.column_type(),
+            value,
+        }
+    }
+
+    fn into_text(self) -> Result<String, Error> {
+        match self.value {
+            Value::Bytes(bytes) => String::from_utf8(bytes).map_err(de::Error::custom),
+            Value::Int(x) => Ok(x.to_string()),
+            Value::UInt(x) => Ok(x.to_string()),
+            Value::Float(x) => Ok(x.to_string()),
+            Value::Double(x) => Ok(x.to_string()),
+            Value::Date(year, month, day, hour, minute, second, micros) => {
+                let mut text = format!("{:04}-{:02}-{:02}", year, month, day);
+                if self.column_type != ColumnType::MYSQL_TYPE_DATE {
+                    text.push_str(&format!(" {:02}:{:02}:{:02}", hour, minute, second));
+                    if micros > 0 {
+                        text.push_str(&format!(".{:06}", micros));
+                    }
+                }
+                Ok(text)
+            }
+            Value::Time(negative, days, hours, minutes, seconds, micros) => {
+                let sign = if negative { "-" } else { "" };
+                let hours = days * 24 + u32::from(hours);
+                let mut text = format!("{}{:02}:{:02}:{:02}", sign, hours, minutes, seconds);
+                if micros > 0 {
+                    text.push_str(&format!(".{:06}", micros));
+                }
+                Ok(text)
+            }
+            Value::NULL => Err(de::Error::custom("unexpected NULL")),
+        }
+    }
+
+    fn parse<T: std::str::FromStr>(self) -> Result<T, Error>
+    where
+        T::Err: std::fmt::Display,
+    {
+        self.into_text()?.trim().parse().map_err(de::Error::custom)
+    }
+
+    fn json(bytes: &[u8]) -> Result<serde_json::Value, Error> {
+        serde_json::from_slice(bytes).map_err(de::Error::custom)
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident => $visit:ident: $ty:ty,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.value {
+                    // DECIMAL and text protocol values arrive as strings.
+                    Value::Bytes(_) => visitor.$visit(self.parse::<$ty>()?),
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::NULL => visitor.visit_unit(),
+            Value::Int(x) => visitor.visit_i64(x),
+            Value::UInt(x) => visitor.visit_u64(x),
+            Value::Float(x) => visitor.visit_f32(x),
+            Value::Double(x) => visitor.visit_f64(x),
+            Value::Bytes(bytes) if self.column_type == ColumnType::MYSQL_TYPE_JSON => {
+                Self::json(&bytes)?
+                    .deserialize_any(visitor)
+                    .map_err(de::Error::custom)
+            }
+            Value::Bytes(bytes) => match String::from_utf8(bytes) {
+                Ok(text) => visitor.visit_string(text),
+                Err(err) => visitor.visit_byte_buf(err.into_bytes()),
+            },
+            Value::Date(..) | Value::Time(..) => visitor.visit_string(self.into_text()?),
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_i128 => visit_i128: i128,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_u128 => visit_u128: u128,
+        deserialize_f32 => visit_f32: f32,
+        deserialize_f64 => visit_f64: f64,
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Int(x) => visitor.visit_bool(x != 0),
+            Value::UInt(x) => visitor.visit_bool(x != 0),
+            Value::Bytes(_) => match self.into_text()?.as_str() {
+                "1" | "true" => visitor.visit_bool(true),
+                "0" | "false" => visitor.visit_bool(false),
+                text => Err(de::Error::invalid_value(
+                    de::Unexpected::Str(text),
+                    &"a boolean",
+                )),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::NULL => visitor.visit_unit(),
+            _ => visitor.visit_string(self.into_text()?),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::NULL => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::NULL => visitor.visit_unit(),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Bytes(bytes) => Self::json(&bytes)?
+                .deserialize_seq(visitor)
+                .map_err(de::Error::custom),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Bytes(bytes) => Self::json(&bytes)?
+                .deserialize_map(visitor)
+                .map_err(de::Error::custom),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Bytes(bytes) => Self::json(&bytes)?
+                .deserialize_struct(name, fields, visitor)
+                .map_err(de::Error::custom),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value {
+            // Plain strings name a unit variant, as in an `ENUM` column.
+            Value::Bytes(bytes) if !bytes.starts_with(b"{") => {
+                let text = String::from_utf8(bytes).map_err(de::Error::custom)?;
+                text.into_deserializer()
+                    .deserialize_enum(name, variants, visitor)
+            }
+            Value::Bytes(bytes) => Self::json(&bytes)?
+                .deserialize_enum(name, variants, visitor)
+                .map_err(de::Error::custom),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mysql_common::row::new_row;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn row(values: Vec<(&str, ColumnType, Value)>) -> Row {
+        let columns = values
+            .iter()
+            .map(|(name, column_type, _)| Column::new(*column_type).with_name(name.as_bytes()))
+            .collect::<Vec<_>>();
+        new_row(
+            values.into_iter().map(|(_, _, value)| value).collect(),
+            Arc::from(columns),
+        )
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Settings {
+        theme: String,
+        tags: Vec<String>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Role {
+        Admin,
+        Member,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct User {
+        id: u32,
+        #[serde(rename = "user_name")]
+        name: String,
+        email: Option<String>,
+        active: bool,
+        balance: f64,
+        role: Role,
+        created_at: String,
+        settings: Settings,
+        extra: HashMap<String, i32>,
+    }
+
+    #[test]
+    fn test_from_row() {
+        let user: User = from_row(row(vec![
+            ("id", ColumnType::MYSQL_TYPE_LONG, Value::Int(1)),
+            (
+                "user_name",
+                ColumnType::MYSQL_TYPE_VAR_STRING,
+                Value::from("alice"),
+            ),
+            ("email", ColumnType::MYSQL_TYPE_VAR_STRING, Value::NULL),
+            ("active", ColumnType::MYSQL_TYPE_TINY, Value::Int(1)),
+            (
+                "balance",
+                ColumnType::MYSQL_TYPE_NEWDECIMAL,
+                Value::from("12.50"),
+            ),
+            ("role", ColumnType::MYSQL_TYPE_STRING, Value::from("admin")),
+            (
+                "created_at",
+                ColumnType::MYSQL_TYPE_DATETIME,
+                Value::Date(2023, 4, 5, 6, 7, 8, 0),
+            ),
+            (
+                "settings",
+                ColumnType::MYSQL_TYPE_JSON,
+                Value::from(r#"{"theme": "dark", "tags": ["a", "b"]}"#),
+            ),
+            (
+                "extra",
+                ColumnType::MYSQL_TYPE_BLOB,
+                Value::from(r#"{"x": 1}"#),
+            ),
+            ("ignored", ColumnType::MYSQL_TYPE_LONG, Value::Int(5)),
+        ]))
+        .unwrap();
+        assert_eq!(
+            user,
+            User {
+                id: 1,
+                name: "alice".to_string(),
+                email: None,
+                active: true,
+                balance: 12.5,
+                role: Role::Admin,
+                created_at: "2023-04-05 06:07:08".to_string(),
+                settings: Settings {
+                    theme: "dark".to_string(),
+                    tags: vec!["a".to_string(), "b".to_string()],
+                },
+                extra: HashMap::from([("x".to_string(), 1)]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_tuple_and_json_value() {
+        let (id, doc): (i64, serde_json::Value) = from_row(row(vec![
+            ("id", ColumnType::MYSQL_TYPE_LONGLONG, Value::Int(3)),
+            ("doc", ColumnType::MYSQL_TYPE_JSON, Value::from("[1, 2]")),
+        ]))
+        .unwrap();
+        assert_eq!(id, 3);
+        assert_eq!(doc, serde_json::json!([1, 2]));
+    }
+
+    #[test]
+    fn test_error_names_column() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Small {
+            id: u8,
+        }
+        let err = from_row::<Small>(row(vec![(
+            "id",
+            ColumnType::MYSQL_TYPE_LONG,
+            Value::Int(300),
+        )]))
+        .unwrap_err();
+        assert!(err.to_string().contains("column `id`"), "{}", err);
+    }
+}
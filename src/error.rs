@@ -0,0 +1,258 @@
+#[cfg(feature = "async")]
+use mysql_async::{DriverError, IoError, ServerError};
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+#[cfg(feature = "async")]
+use crate::fixtures::FixtureError;
+use crate::in_list::ParamsError;
+#[cfg(feature = "async")]
+use crate::migrate::MigrateError;
+use crate::query::QueryError;
+use crate::{ConfigError, FromRowError, SerializeError};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+const ER_LOCK_WAIT_TIMEOUT: u16 = 1205;
+const ER_DUP_ENTRY: u16 = 1062;
+const ER_LOCK_DEADLOCK: u16 = 1213;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The connection URL or options are invalid.
+    Config(ConfigError),
+    /// The connection to the server could not be established or was lost.
+    Io(io::Error),
+    /// The driver rejected the operation, e.g. because the pool was disconnected.
+    #[cfg(feature = "async")]
+    Driver(DriverError),
+    /// The server answered with an error packet.
+    Server {
+        code: u16,
+        state: String,
+        message: String,
+    },
+    /// A row could not be converted into the requested type.
+    FromRow(FromRowError),
+    /// Parameters could not be bound to the query, e.g. because one is missing.
+    Params(ParamsError),
+    /// A value could not be serialized into statement parameters.
+    Serialize(SerializeError),
+    /// A row could not be deserialized with serde.
+    Deserialize(serde::de::value::Error),
+    /// A query expected to return exactly one row returned none.
+    NotFound,
+    /// A query expected to return at most one row returned more.
+    TooManyRows,
+    /// The operation did not complete within the configured timeout.
+    Timeout(Duration),
+    /// Migrations could not be loaded or applied.
+    #[cfg(feature = "async")]
+    Migrate(MigrateError),
+    /// Fixtures could not be read or loaded.
+    #[cfg(feature = "async")]
+    Fixture(FixtureError),
+    /// A query builder was given an incomplete statement.
+    Query(QueryError),
+    Other(Box<dyn std::error::Error + Send + Sync + 'static>),
+}
+
+impl Error {
+    /// The MySQL error code if this is a server error.
+    pub fn code(&self) -> Option<u16> {
+        match self {
+            Error::Server { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// The SQLSTATE if this is a server error.
+    pub fn sql_state(&self) -> Option<&str> {
+        match self {
+            Error::Server { state, .. } => Some(state),
+            _ => None,
+        }
+    }
+
+    pub fn is_duplicate_key(&self) -> bool {
+        self.code() == Some(ER_DUP_ENTRY)
+    }
+
+    pub fn is_deadlock(&self) -> bool {
+        self.code() == Some(ER_LOCK_DEADLOCK)
+    }
+
+    pub fn is_lock_wait_timeout(&self) -> bool {
+        self.code() == Some(ER_LOCK_WAIT_TIMEOUT)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(err) => write!(f, "configuration error: {}", err),
+            Error::Io(err) => write!(f, "connection error: {}", err),
+            #[cfg(feature = "async")]
+            Error::Driver(err) => write!(f, "driver error: {}", err),
+            Error::Server {
+                code,
+                state,
+                message,
+            } => write!(f, "server error {} ({}): {}", code, state, message),
+            Error::FromRow(err) => write!(f, "{}", err),
+            Error::Params(err) => write!(f, "{}", err),
+            Error::Serialize(err) => write!(f, "failed to serialize parameters: {}", err),
+            Error::Deserialize(err) => write!(f, "failed to deserialize row: {}", err),
+            Error::NotFound => write!(f, "query returned no rows"),
+            Error::TooManyRows => write!(f, "query returned more than one row"),
+            Error::Timeout(timeout) => write!(f, "operation timed out after {:?}", timeout),
+            #[cfg(feature = "async")]
+            Error::Migrate(err) => write!(f, "{}", err),
+            #[cfg(feature = "async")]
+            Error::Fixture(err) => write!(f, "{}", err),
+            Error::Query(err) => write!(f, "{}", err),
+            Error::Other(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Config(err) => Some(err),
+            Error::Io(err) => Some(err),
+            #[cfg(feature = "async")]
+            Error::Driver(err) => Some(err),
+            Error::FromRow(err) => Some(err),
+            Error::Params(err) => Some(err),
+            Error::Serialize(err) => Some(err),
+            Error::Deserialize(err) => Some(err),
+            #[cfg(feature = "async")]
+            Error::Migrate(err) => Some(err),
+            #[cfg(feature = "async")]
+            Error::Fixture(err) => Some(err),
+            Error::Query(err) => Some(err),
+            Error::Other(err) => Some(err.as_ref()),
+            Error::Server { .. } | Error::NotFound | Error::TooManyRows | Error::Timeout(_) => None,
+        }
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(err: ConfigError) -> Self {
+        Error::Config(err)
+    }
+}
+
+#[cfg(feature = "async")]
+impl From<MigrateError> for Error {
+    fn from(err: MigrateError) -> Self {
+        Error::Migrate(err)
+    }
+}
+
+impl From<ParamsError> for Error {
+    fn from(err: ParamsError) -> Self {
+        Error::Params(err)
+    }
+}
+
+impl From<QueryError> for Error {
+    fn from(err: QueryError) -> Self {
+        Error::Query(err)
+    }
+}
+
+#[cfg(feature = "async")]
+impl From<FixtureError> for Error {
+    fn from(err: FixtureError) -> Self {
+        Error::Fixture(err)
+    }
+}
+
+impl From<FromRowError> for Error {
+    fn from(err: FromRowError) -> Self {
+        Error::FromRow(err)
+    }
+}
+
+impl From<serde::de::value::Error> for Error {
+    fn from(err: serde::de::value::Error) -> Self {
+        Error::Deserialize(err)
+    }
+}
+
+#[cfg(feature = "async")]
+impl From<mysql_async::Error> for Error {
+    fn from(err: mysql_async::Error) -> Self {
+        match err {
+            mysql_async::Error::Driver(DriverError::FromRow { row }) => {
+                Error::FromRow(FromRowError(row))
+            }
+            mysql_async::Error::Driver(err) => Error::Driver(err),
+            mysql_async::Error::Io(IoError::Io(err)) => Error::Io(err),
+            mysql_async::Error::Io(err) => Error::Io(io::Error::other(err)),
+            mysql_async::Error::Server(ServerError {
+                code,
+                message,
+                state,
+            }) => Error::Server {
+                code,
+                state,
+                message,
+            },
+            mysql_async::Error::Other(err) => Error::Other(err),
+            err => Error::Other(Box::new(err)),
+        }
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl From<mysql_sync::Error> for Error {
+    fn from(err: mysql_sync::Error) -> Self {
+        match err {
+            mysql_sync::Error::IoError(err) => Error::Io(err),
+            mysql_sync::Error::MySqlError(mysql_sync::MySqlError {
+                code,
+                message,
+                state,
+            }) => Error::Server {
+                code,
+                state,
+                message,
+            },
+            mysql_sync::Error::FromRowError(row) => Error::FromRow(FromRowError(row)),
+            err => Error::Other(Box::new(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_error(code: u16) -> Error {
+        Error::from(mysql_async::Error::Server(ServerError {
+            code,
+            message: "error".to_string(),
+            state: "40001".to_string(),
+        }))
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: std::error::Error + Send + Sync + 'static>() {}
+        assert_send_sync::<Error>();
+    }
+
+    #[test]
+    fn test_server_error() {
+        let err = server_error(ER_LOCK_DEADLOCK);
+        assert_eq!(err.code(), Some(1213));
+        assert_eq!(err.sql_state(), Some("40001"));
+        assert!(err.is_deadlock());
+        assert!(!err.is_duplicate_key());
+        assert!(server_error(ER_DUP_ENTRY).is_duplicate_key());
+    }
+}
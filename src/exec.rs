@@ -0,0 +1,184 @@
+use mysql_async::{prelude::*, Conn, InfileData};
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::iter;
+use std::time::Duration;
+
+use crate::batch::{BulkInsert, DEFAULT_MAX_ALLOWED_PACKET};
+use crate::infile::{LoadDataOptions, Registration};
+use crate::{de, in_list, rt, trace, Error, ExecResult, Params, Result, Row, Value};
+
+impl ExecResult {
+    fn from_conn(conn: &Conn) -> Self {
+        ExecResult {
+            affected_rows: conn.affected_rows(),
+            last_insert_id: conn.last_insert_id(),
+            warnings: conn.get_warnings(),
+            info: conn.info().into_owned(),
+        }
+    }
+}
+
+/// A connection that queries can be issued on: a pooled connection or an open transaction.
+pub(crate) trait Connection: Queryable {
+    fn conn(&self) -> &Conn;
+}
+
+impl Connection for Conn {
+    fn conn(&self) -> &Conn {
+        self
+    }
+}
+
+impl Connection for mysql_async::Transaction<'_> {
+    fn conn(&self) -> &Conn {
+        self
+    }
+}
+
+/// Records `query` on the current query span before it is sent on `conn`.
+fn trace_statement<C: Connection>(conn: &C, query: &str) {
+    trace::statement(query, conn.conn().id());
+    trace::server(conn.conn().opts());
+}
+
+pub(crate) async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    operation: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    rt::compat(async {
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, operation)
+                .await
+                .map_err(|_| Error::Timeout(timeout))?,
+            None => operation.await,
+        }
+    })
+    .await
+}
+
+pub(crate) async fn select<T: FromRow + Send, C: Connection>(
+    conn: &mut C,
+    query: &str,
+    params_map: Option<Params>,
+    max_list_len: Option<usize>,
+) -> Result<Vec<T>> {
+    let (query, params) = in_list::expand(query, params_map, max_list_len)?;
+    trace_statement(conn, &query);
+    trace::parameters(&params);
+    let rows = conn
+        .exec_map(query.as_ref(), params, |row: Row| T::from_row_opt(row))
+        .await?;
+    trace::returned_rows(rows.len());
+    Ok(rows.into_iter().collect::<Result<_, _>>()?)
+}
+
+pub(crate) async fn select_as<T: DeserializeOwned + Send, C: Connection>(
+    conn: &mut C,
+    query: &str,
+    params_map: Option<Params>,
+    max_list_len: Option<usize>,
+) -> Result<Vec<T>> {
+    let (query, params) = in_list::expand(query, params_map, max_list_len)?;
+    trace_statement(conn, &query);
+    trace::parameters(&params);
+    let rows = conn
+        .exec_map(query.as_ref(), params, |row: Row| de::from_row(row))
+        .await?;
+    trace::returned_rows(rows.len());
+    Ok(rows.into_iter().collect::<Result<_, _>>()?)
+}
+
+pub(crate) async fn execute<C: Connection>(
+    conn: &mut C,
+    query: &str,
+    params_map: Option<Params>,
+    max_list_len: Option<usize>,
+) -> Result<ExecResult> {
+    let (query, params) = in_list::expand(query, params_map, max_list_len)?;
+    trace_statement(conn, &query);
+    trace::parameters(&params);
+    conn.exec_drop(query.as_ref(), params).await?;
+    trace::affected_rows(conn.conn().affected_rows());
+    Ok(ExecResult::from_conn(conn.conn()))
+}
+
+pub(crate) async fn execute_batch<C, I>(
+    conn: &mut C,
+    query: &str,
+    params: I,
+    max_list_len: Option<usize>,
+) -> Result<u64>
+where
+    C: Connection,
+    I: IntoIterator<Item = Params> + Send,
+    I::IntoIter: Send,
+{
+    // Like `exec_batch`, but summing the affected rows of every execution. The statement is
+    // prepared for the first parameter set, once its lists are expanded.
+    let mut params = params
+        .into_iter()
+        .map(|params| in_list::expand(query, Some(params), max_list_len));
+    let Some(first) = params.next() else {
+        return Ok(0);
+    };
+    let (prepared, first) = first?;
+    trace_statement(conn, &prepared);
+    let statement = conn.prep(prepared.as_ref()).await?;
+    let mut affected_rows = 0;
+    for expanded in iter::once(Ok((prepared.clone(), first))).chain(params) {
+        let (query, params) = expanded?;
+        in_list::check_batch(&prepared, &query)?;
+        trace::parameters(&params);
+        conn.exec_drop(&statement, params).await?;
+        affected_rows += conn.conn().affected_rows();
+    }
+    trace::affected_rows(affected_rows);
+    Ok(affected_rows)
+}
+
+pub(crate) async fn bulk_insert<C, I>(
+    conn: &mut C,
+    table: &str,
+    columns: &[&str],
+    rows: I,
+) -> Result<u64>
+where
+    C: Connection,
+    I: IntoIterator<Item = Vec<Value>> + Send,
+    I::IntoIter: Send,
+{
+    let insert = BulkInsert::new(table, columns)?;
+    let max_allowed_packet = match conn.conn().opts().max_allowed_packet() {
+        Some(max_allowed_packet) => max_allowed_packet,
+        None => conn
+            .query_first("SELECT @@max_allowed_packet")
+            .await?
+            .unwrap_or(DEFAULT_MAX_ALLOWED_PACKET),
+    };
+    let mut affected_rows = 0;
+    for chunk in insert.chunks(rows, max_allowed_packet) {
+        let (query, values) = chunk?;
+        trace_statement(conn, &query);
+        trace::values(&values);
+        conn.exec_drop(query, values).await?;
+        affected_rows += conn.conn().affected_rows();
+    }
+    trace::affected_rows(affected_rows);
+    Ok(affected_rows)
+}
+
+pub(crate) async fn load_data<C: Connection>(
+    conn: &mut C,
+    table: &str,
+    columns: &[&str],
+    data: InfileData,
+    options: &LoadDataOptions,
+) -> Result<ExecResult> {
+    let registration = Registration::new(conn.conn().id(), data)?;
+    let statement = options.statement(registration.file_name(), table, columns);
+    trace_statement(conn, &statement);
+    registration.run(conn.query_drop(statement)).await?;
+    trace::affected_rows(conn.conn().affected_rows());
+    Ok(ExecResult::from_conn(conn.conn()))
+}
@@ -0,0 +1,195 @@
+use futures::future::BoxFuture;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+
+use crate::result::{self, ExecResult};
+use crate::{Database, FromRow, FromValue, Params, Result, Transaction, TxOptions};
+
+/// Something queries can be run on, so application code can take `&impl Executor` and be
+/// handed a [`Database`], an open [`Transaction`] or, in tests, a
+/// [`MockExecutor`](crate::test_support::MockExecutor).
+///
+/// ```ignore
+/// async fn rename(db: &impl Executor, id: u64, name: &str) -> mysql::Result<bool> {
+///     let result = db
+///         .execute(
+///             "UPDATE users SET name = :name WHERE id = :id",
+///             Some(params! { "name" => name, "id" => id }),
+///         )
+///         .await?;
+///     Ok(result.affected_rows == 1)
+/// }
+/// ```
+pub trait Executor: Send + Sync {
+    /// What [`Executor::transaction`] hands to its closure.
+    type Transaction: Executor;
+
+    fn select<T: FromRow + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<Vec<T>>> + Send;
+
+    fn select_as<T: DeserializeOwned + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<Vec<T>>> + Send;
+
+    fn execute(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<ExecResult>> + Send;
+
+    /// Runs `f` in a transaction, committing if it returns `Ok` and rolling back otherwise.
+    ///
+    /// On a [`Transaction`] this runs `f` as part of the open transaction: `options` are
+    /// ignored, nothing is committed and a deadlock is not retried.
+    fn transaction<T, F>(&self, options: TxOptions, f: F) -> impl Future<Output = Result<T>> + Send
+    where
+        T: Send,
+        F: for<'t> FnMut(&'t Self::Transaction) -> BoxFuture<'t, Result<T>> + Send;
+
+    fn select_one<T: FromRow + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<T>> + Send {
+        async move { result::one(self.select(query, params_map).await?) }
+    }
+
+    fn select_optional<T: FromRow + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<Option<T>>> + Send {
+        async move { result::optional(self.select(query, params_map).await?) }
+    }
+
+    fn select_scalar<T: FromValue + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<T>> + Send {
+        async move {
+            let (value,) = self.select_one::<(T,)>(query, params_map).await?;
+            Ok(value)
+        }
+    }
+}
+
+impl Executor for Database {
+    type Transaction = Transaction;
+
+    fn select<T: FromRow + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<Vec<T>>> + Send {
+        Database::select(self, query, params_map)
+    }
+
+    fn select_as<T: DeserializeOwned + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<Vec<T>>> + Send {
+        Database::select_as(self, query, params_map)
+    }
+
+    fn execute(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<ExecResult>> + Send {
+        Database::execute(self, query, params_map)
+    }
+
+    fn transaction<T, F>(&self, options: TxOptions, f: F) -> impl Future<Output = Result<T>> + Send
+    where
+        T: Send,
+        F: for<'t> FnMut(&'t Transaction) -> BoxFuture<'t, Result<T>> + Send,
+    {
+        Database::transaction(self, options, f)
+    }
+}
+
+impl Executor for Transaction {
+    type Transaction = Transaction;
+
+    fn select<T: FromRow + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<Vec<T>>> + Send {
+        Transaction::select(self, query, params_map)
+    }
+
+    fn select_as<T: DeserializeOwned + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<Vec<T>>> + Send {
+        Transaction::select_as(self, query, params_map)
+    }
+
+    fn execute(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<ExecResult>> + Send {
+        Transaction::execute(self, query, params_map)
+    }
+
+    async fn transaction<T, F>(&self, _options: TxOptions, mut f: F) -> Result<T>
+    where
+        T: Send,
+        F: for<'t> FnMut(&'t Transaction) -> BoxFuture<'t, Result<T>> + Send,
+    {
+        f(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params;
+    use crate::test_support::{FakeServer, MockExecutor, Response, Script};
+
+    async fn rename(db: &impl Executor, id: u64, name: &str) -> Result<bool> {
+        db.transaction(TxOptions::default(), |tx| {
+            let name = name.to_string();
+            Box::pin(async move {
+                let result = tx
+                    .execute(
+                        "UPDATE users SET name = :name WHERE id = :id",
+                        Some(params! { "name" => name, "id" => id }),
+                    )
+                    .await?;
+                Ok(result.affected_rows == 1)
+            })
+        })
+        .await
+    }
+
+    fn script() -> Script {
+        Script::new().with_response(
+            "UPDATE users SET name = ? WHERE id = ?",
+            Response::affected_rows(1),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_database_and_mock_agree() {
+        let server = FakeServer::start(script());
+        assert!(rename(&server.database(), 1, "alice").await.unwrap());
+        let mock = MockExecutor::new(script());
+        assert!(rename(&mock, 1, "alice").await.unwrap());
+        assert_eq!(server.received(), mock.received());
+
+        let tx = server.database().begin(TxOptions::default()).await.unwrap();
+        assert!(rename(&tx, 1, "bob").await.unwrap());
+        tx.rollback().await.unwrap();
+    }
+}
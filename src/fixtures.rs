@@ -0,0 +1,461 @@
+//! Seed data for tests and development databases.
+//!
+//! YAML and JSON fixtures map table names to lists of rows, with one key per column:
+//!
+//! ```yaml
+//! users:
+//!   - { id: 1, name: alice }
+//! posts:
+//!   - { id: 1, user_id: 1, title: Hello }
+//! ```
+//!
+//! Nested lists and maps are stored as JSON. The rows of every data fixture in a set are
+//! inserted in one transaction, with referenced tables before the tables referencing them
+//! according to the foreign keys in `information_schema`, so files and tables can be
+//! written in any order. SQL fixtures then run in order as plain scripts.
+//!
+//! ```ignore
+//! let fixtures = Fixtures::from_dir("fixtures")?.select(&["users", "posts"])?;
+//! fixtures.load(&database).await?;
+//! ```
+
+use serde_json::Map;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use mysql_async::prelude::*;
+
+use crate::{rt, to_params, Database, Params, Result, TxOptions, Value};
+
+/// Foreign keys between tables of the current database, as `(table, referenced table)`.
+const FOREIGN_KEYS: &str = "SELECT TABLE_NAME, REFERENCED_TABLE_NAME
+    FROM information_schema.KEY_COLUMN_USAGE
+    WHERE TABLE_SCHEMA = DATABASE() AND REFERENCED_TABLE_SCHEMA = DATABASE()";
+
+#[derive(Debug)]
+pub enum FixtureError {
+    /// A fixture file is not `.sql`, `.json`, `.yaml` or `.yml`.
+    UnknownFormat(PathBuf),
+    Read {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A YAML or JSON fixture is not a map of table names to lists of rows.
+    Invalid {
+        name: String,
+        message: String,
+    },
+    /// No fixture has the selected name.
+    NotFound(String),
+    /// The tables reference each other, so no insert order satisfies their foreign keys.
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixtureError::UnknownFormat(path) => write!(
+                f,
+                "`{}` is not a `.sql`, `.json`, `.yaml` or `.yml` fixture",
+                path.display()
+            ),
+            FixtureError::Read { path, source } => {
+                write!(f, "failed to read `{}`: {}", path.display(), source)
+            }
+            FixtureError::Invalid { name, message } => {
+                write!(f, "fixture `{}` is invalid: {}", name, message)
+            }
+            FixtureError::NotFound(name) => write!(f, "no fixture is named `{}`", name),
+            FixtureError::Cycle(tables) => write!(
+                f,
+                "tables {} reference each other, so their fixtures cannot be ordered",
+                tables.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FixtureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FixtureError::Read { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Sql,
+    Json,
+    Yaml,
+}
+
+impl Format {
+    /// The format of a file with the given extension.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "sql" => Some(Format::Sql),
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// A single fixture file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    /// The file name without its extension.
+    pub name: Cow<'static, str>,
+    pub format: Format,
+    pub source: Cow<'static, str>,
+}
+
+type Rows = Vec<Map<String, serde_json::Value>>;
+
+impl Fixture {
+    pub fn new(
+        name: impl Into<Cow<'static, str>>,
+        format: Format,
+        source: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Fixture {
+            name: name.into(),
+            format,
+            source: source.into(),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, FixtureError> {
+        let path = path.as_ref();
+        let (Some(name), Some(format)) = (
+            path.file_stem().and_then(|stem| stem.to_str()),
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .and_then(Format::from_extension),
+        ) else {
+            return Err(FixtureError::UnknownFormat(path.to_path_buf()));
+        };
+        let source = std::fs::read_to_string(path).map_err(|source| FixtureError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(Fixture::new(name.to_string(), format, source))
+    }
+
+    /// The rows of a YAML or JSON fixture by table, or `None` for SQL.
+    fn tables(&self) -> Result<Option<BTreeMap<String, Rows>>, FixtureError> {
+        let invalid = |message: String| FixtureError::Invalid {
+            name: self.name.to_string(),
+            message,
+        };
+        let tables: BTreeMap<String, Rows> = match self.format {
+            Format::Sql => return Ok(None),
+            Format::Json => serde_json::from_str(&self.source),
+            Format::Yaml => serde_yaml::from_str(&self.source).map_err(serde::de::Error::custom),
+        }
+        .map_err(|err: serde_json::Error| invalid(err.to_string()))?;
+        for (table, rows) in &tables {
+            if let Some(i) = rows.iter().position(Map::is_empty) {
+                return Err(invalid(format!("row {} of `{}` has no columns", i, table)));
+            }
+        }
+        Ok(Some(tables))
+    }
+}
+
+/// A set of fixtures loaded together.
+#[derive(Debug, Clone, Default)]
+pub struct Fixtures {
+    fixtures: Vec<Fixture>,
+}
+
+impl Fixtures {
+    pub fn new(fixtures: Vec<Fixture>) -> Self {
+        Fixtures { fixtures }
+    }
+
+    /// Reads every `.sql`, `.json`, `.yaml` and `.yml` file in `dir`, ordered by file name.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, FixtureError> {
+        let dir = dir.as_ref();
+        let read_error = |source| FixtureError::Read {
+            path: dir.to_path_buf(),
+            source,
+        };
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(read_error)? {
+            let path = entry.map_err(read_error)?.path();
+            let known = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .and_then(Format::from_extension)
+                .is_some();
+            if known && path.is_file() {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        paths
+            .into_iter()
+            .map(Fixture::from_file)
+            .collect::<Result<_, _>>()
+            .map(Fixtures::new)
+    }
+
+    pub fn fixtures(&self) -> &[Fixture] {
+        &self.fixtures
+    }
+
+    /// Only the fixtures with the given names, in the order the names are given.
+    pub fn select(&self, names: &[&str]) -> Result<Fixtures, FixtureError> {
+        let mut selected = Vec::new();
+        for name in names {
+            let before = selected.len();
+            selected.extend(
+                self.fixtures
+                    .iter()
+                    .filter(|fixture| fixture.name == *name)
+                    .cloned(),
+            );
+            if selected.len() == before {
+                return Err(FixtureError::NotFound(name.to_string()));
+            }
+        }
+        Ok(Fixtures::new(selected))
+    }
+
+    /// Inserts the rows of the YAML and JSON fixtures, then runs the SQL fixtures.
+    pub async fn load(&self, database: &Database) -> Result<()> {
+        let mut tables = BTreeMap::<String, Rows>::new();
+        for fixture in &self.fixtures {
+            for (table, rows) in fixture.tables()?.into_iter().flatten() {
+                tables.entry(table).or_default().extend(rows);
+            }
+        }
+
+        if !tables.is_empty() {
+            let references: Vec<(String, String)> = database.select(FOREIGN_KEYS, None).await?;
+            let order = insert_order(tables.keys(), &references)?;
+            let tx = database.begin(TxOptions::default()).await?;
+            for table in order {
+                for (columns, rows) in group_by_columns(&tables[table])? {
+                    let columns = columns.iter().map(String::as_str).collect::<Vec<_>>();
+                    tx.bulk_insert(table, &columns, rows).await?;
+                }
+            }
+            tx.commit().await?;
+        }
+
+        let scripts = self
+            .fixtures
+            .iter()
+            .filter(|fixture| fixture.format == Format::Sql);
+        let mut conn = None;
+        for fixture in scripts {
+            let conn = match &mut conn {
+                Some(conn) => conn,
+                None => conn.insert(database.get_conn().await?),
+            };
+            rt::compat(conn.query_drop(fixture.source.as_ref())).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Orders `tables` so every table comes after the tables it references.
+fn insert_order<'a>(
+    tables: impl IntoIterator<Item = &'a String>,
+    references: &[(String, String)],
+) -> Result<Vec<&'a String>, FixtureError> {
+    let mut pending = tables.into_iter().collect::<BTreeSet<_>>();
+    let mut order = Vec::with_capacity(pending.len());
+    while !pending.is_empty() {
+        let ready = pending
+            .iter()
+            .copied()
+            .filter(|table| {
+                !references
+                    .iter()
+                    .any(|(from, to)| from == *table && to != *table && pending.contains(to))
+            })
+            .collect::<Vec<_>>();
+        if ready.is_empty() {
+            return Err(FixtureError::Cycle(pending.into_iter().cloned().collect()));
+        }
+        for table in ready {
+            pending.remove(table);
+            order.push(table);
+        }
+    }
+    Ok(order)
+}
+
+/// Rows sharing the same columns, inserted with one bulk insert.
+type Group = (Vec<String>, Vec<Vec<Value>>);
+
+/// Splits rows into runs sharing the same columns.
+fn group_by_columns(rows: &Rows) -> Result<Vec<Group>> {
+    let mut groups: Vec<Group> = Vec::new();
+    for row in rows {
+        let Params::Named(mut params) = to_params(row)? else {
+            unreachable!("rows have at least one column")
+        };
+        let columns = row.keys().cloned().collect::<Vec<_>>();
+        let values = columns
+            .iter()
+            .map(|column| params.remove(column.as_bytes()).unwrap_or(Value::NULL))
+            .collect();
+        match groups.last_mut() {
+            Some((last, rows)) if *last == columns => rows.push(values),
+            _ => groups.push((columns, vec![values])),
+        }
+    }
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FakeServer, Response, Script};
+
+    const USERS: &str = "
+users:
+  - { id: 1, name: alice, tags: [admin] }
+  - { id: 2, name: bob, tags: [] }
+  - { id: 3, name: carol }
+";
+
+    const POSTS: &str = r#"{"posts": [{"id": 1, "user_id": 1, "title": "Hello"}]}"#;
+
+    fn references() -> Vec<(String, String)> {
+        vec![
+            ("posts".to_string(), "users".to_string()),
+            ("comments".to_string(), "posts".to_string()),
+            ("users".to_string(), "users".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_tables() {
+        let tables = Fixture::new("users", Format::Yaml, USERS)
+            .tables()
+            .unwrap()
+            .unwrap();
+        let groups = group_by_columns(&tables["users"]).unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, ["id", "name", "tags"]);
+        assert_eq!(
+            groups[0].1[0],
+            [
+                Value::UInt(1),
+                Value::from("alice"),
+                Value::from("[\"admin\"]")
+            ]
+        );
+        assert_eq!(groups[1].0, ["id", "name"]);
+
+        assert!(Fixture::new("posts", Format::Json, POSTS)
+            .tables()
+            .unwrap()
+            .is_some());
+        assert!(Fixture::new("seed", Format::Sql, "DELETE FROM users")
+            .tables()
+            .unwrap()
+            .is_none());
+        let err = Fixture::new("broken", Format::Json, r#"{"users": [{}]}"#)
+            .tables()
+            .unwrap_err();
+        assert!(matches!(err, FixtureError::Invalid { .. }), "{}", err);
+        let err = Fixture::new("broken", Format::Yaml, "users: 1")
+            .tables()
+            .unwrap_err();
+        assert!(matches!(err, FixtureError::Invalid { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_insert_order() {
+        let tables = ["comments", "posts", "tags", "users"].map(String::from);
+        let order = insert_order(&tables, &references()).unwrap();
+        assert_eq!(order, ["tags", "users", "posts", "comments"]);
+
+        let mut references = references();
+        references.push(("users".to_string(), "comments".to_string()));
+        let err = insert_order(&tables, &references).unwrap_err();
+        assert!(
+            matches!(&err, FixtureError::Cycle(tables) if tables == &["comments", "posts", "users"])
+        );
+    }
+
+    #[test]
+    fn test_select() {
+        let fixtures = Fixtures::new(vec![
+            Fixture::new("users", Format::Yaml, USERS),
+            Fixture::new("posts", Format::Json, POSTS),
+            Fixture::new("users", Format::Sql, "UPDATE users SET name = UPPER(name)"),
+        ]);
+        let selected = fixtures.select(&["posts", "users"]).unwrap();
+        let formats = selected
+            .fixtures()
+            .iter()
+            .map(|fixture| fixture.format)
+            .collect::<Vec<_>>();
+        assert_eq!(formats, [Format::Json, Format::Yaml, Format::Sql]);
+        assert!(matches!(
+            fixtures.select(&["comments"]),
+            Err(FixtureError::NotFound(name)) if name == "comments"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let server = FakeServer::start(
+            Script::new()
+                .with_response(
+                    FOREIGN_KEYS,
+                    Response::rows(
+                        &["TABLE_NAME", "REFERENCED_TABLE_NAME"],
+                        references()
+                            .into_iter()
+                            .map(|(from, to)| vec![from.into(), to.into()])
+                            .collect(),
+                    ),
+                )
+                .with_response("UPDATE users SET name = UPPER(name)", Response::ok())
+                .with_handler(|query, params| {
+                    query
+                        .starts_with("INSERT INTO")
+                        .then(|| Response::affected_rows(params.len() as u64))
+                }),
+        );
+        Fixtures::new(vec![
+            Fixture::new("posts", Format::Json, POSTS),
+            Fixture::new("rename", Format::Sql, "UPDATE users SET name = UPPER(name)"),
+            Fixture::new("users", Format::Yaml, USERS),
+        ])
+        .load(&server.database())
+        .await
+        .unwrap();
+
+        let received = server.received();
+        let queries = received
+            .iter()
+            .map(|received| received.query.as_str())
+            .filter(|query| *query != FOREIGN_KEYS)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            queries,
+            [
+                "START TRANSACTION",
+                "INSERT INTO `users` (`id`, `name`, `tags`) VALUES (?, ?, ?), (?, ?, ?)",
+                "INSERT INTO `users` (`id`, `name`) VALUES (?, ?)",
+                "INSERT INTO `posts` (`id`, `title`, `user_id`) VALUES (?, ?, ?)",
+                "COMMIT",
+                "UPDATE users SET name = UPPER(name)",
+            ]
+        );
+    }
+}
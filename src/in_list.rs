@@ -0,0 +1,524 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::batch::MAX_PLACEHOLDERS;
+use crate::{Params, Result, Value};
+
+/// Marks a [`Value`] produced by [`InList`]. The listed values follow it, encoded by `encode`.
+const MARKER: &[u8] = b"\0mysql::InList\0\x01";
+
+/// A list parameter, expanded into one placeholder per element before the query is sent.
+///
+/// ```ignore
+/// let users: Vec<User> = mysql::select(
+///     "SELECT * FROM users WHERE id IN (:ids)",
+///     Some(params! { "ids" => InList(vec![1, 2, 3]) }),
+/// )
+/// .await?;
+/// ```
+///
+/// The query above is sent as `... WHERE id IN (?, ?, ?)`. An empty list is replaced by an
+/// empty subquery, so `IN (:ids)` matches no rows and `NOT IN (:ids)` matches every row. An
+/// empty list that is not the only item in its parentheses is replaced by `NULL` after `IN`,
+/// and rejected after `NOT IN`, where `NULL` would match no rows.
+///
+/// Lists are expanded by the `select*`, `execute` and `execute_batch` methods. A batch prepares
+/// its statement once, so a list must have the same length in every parameter set. Lists
+/// cannot be used as `bulk_insert` values, and are only understood by this crate: passed to
+/// `mysql_async` directly, a list is sent as an opaque string. A query may use at most 65535
+/// placeholders once every list is expanded, and
+/// [`Database::with_max_list_len`](crate::Database::with_max_list_len) limits the length of
+/// each list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InList<T>(pub Vec<T>);
+
+impl<T: Into<Value>> From<InList<T>> for Value {
+    fn from(list: InList<T>) -> Value {
+        let mut bytes = MARKER.to_vec();
+        for value in list.0 {
+            encode(&mut bytes, value.into());
+        }
+        Value::Bytes(bytes)
+    }
+}
+
+fn encode(bytes: &mut Vec<u8>, value: Value) {
+    match value {
+        Value::NULL => bytes.push(0),
+        Value::Bytes(value) => {
+            bytes.push(1);
+            bytes.extend((value.len() as u64).to_le_bytes());
+            bytes.extend(value);
+        }
+        Value::Int(value) => {
+            bytes.push(2);
+            bytes.extend(value.to_le_bytes());
+        }
+        Value::UInt(value) => {
+            bytes.push(3);
+            bytes.extend(value.to_le_bytes());
+        }
+        Value::Float(value) => {
+            bytes.push(4);
+            bytes.extend(value.to_le_bytes());
+        }
+        Value::Double(value) => {
+            bytes.push(5);
+            bytes.extend(value.to_le_bytes());
+        }
+        Value::Date(year, month, day, hour, minute, second, micros) => {
+            bytes.push(6);
+            bytes.extend(year.to_le_bytes());
+            bytes.extend([month, day, hour, minute, second]);
+            bytes.extend(micros.to_le_bytes());
+        }
+        Value::Time(negative, days, hours, minutes, seconds, micros) => {
+            bytes.push(7);
+            bytes.push(negative as u8);
+            bytes.extend(days.to_le_bytes());
+            bytes.extend([hours, minutes, seconds]);
+            bytes.extend(micros.to_le_bytes());
+        }
+    }
+}
+
+/// Reads the values back out of an [`InList`], or returns `None` for any other value.
+fn decode(value: &Value) -> Option<Vec<Value>> {
+    let Value::Bytes(bytes) = value else {
+        return None;
+    };
+    let mut bytes = bytes.strip_prefix(MARKER)?;
+    let mut take = |len: usize| {
+        let head = bytes.get(..len)?;
+        bytes = &bytes[len..];
+        Some(head)
+    };
+    let mut values = Vec::new();
+    while let Some(&[tag]) = take(1) {
+        let value = match tag {
+            0 => Value::NULL,
+            1 => {
+                let len = u64::from_le_bytes(take(8)?.try_into().ok()?);
+                Value::Bytes(take(usize::try_from(len).ok()?)?.to_vec())
+            }
+            2 => Value::Int(i64::from_le_bytes(take(8)?.try_into().ok()?)),
+            3 => Value::UInt(u64::from_le_bytes(take(8)?.try_into().ok()?)),
+            4 => Value::Float(f32::from_le_bytes(take(4)?.try_into().ok()?)),
+            5 => Value::Double(f64::from_le_bytes(take(8)?.try_into().ok()?)),
+            6 => {
+                let year = u16::from_le_bytes(take(2)?.try_into().ok()?);
+                let &[month, day, hour, minute, second] = take(5)? else {
+                    return None;
+                };
+                let micros = u32::from_le_bytes(take(4)?.try_into().ok()?);
+                Value::Date(year, month, day, hour, minute, second, micros)
+            }
+            7 => {
+                let &[negative] = take(1)? else {
+                    return None;
+                };
+                let days = u32::from_le_bytes(take(4)?.try_into().ok()?);
+                let &[hours, minutes, seconds] = take(3)? else {
+                    return None;
+                };
+                let micros = u32::from_le_bytes(take(4)?.try_into().ok()?);
+                Value::Time(negative != 0, days, hours, minutes, seconds, micros)
+            }
+            _ => return None,
+        };
+        values.push(value);
+    }
+    Some(values)
+}
+
+/// Parameters that could not be bound to a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamsError {
+    /// The query uses `:name` but no parameter of that name was given.
+    Missing(String),
+    /// The query has more `?` placeholders than positional parameters were given.
+    NotEnough,
+    /// More positional parameters were given than the query has `?` placeholders.
+    TooMany,
+    /// The query uses named placeholders with positional parameters or the other way round.
+    Mixed,
+    /// A list is longer than the limit set with `with_max_list_len`.
+    ListTooLong { len: usize, max: usize },
+    /// The query has more placeholders than a prepared statement allows once lists are expanded.
+    TooManyPlaceholders(usize),
+    /// A parameter set of a batch has lists of other lengths than the first one.
+    BatchMismatch,
+    /// An empty list follows `NOT IN` next to other values, where it cannot be expanded.
+    EmptyNotIn,
+    /// A list was used where lists are not expanded, e.g. inside another list or as a
+    /// `bulk_insert` value.
+    UnexpectedList,
+}
+
+impl fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamsError::Missing(name) => write!(f, "missing named parameter `{}`", name),
+            ParamsError::NotEnough => write!(f, "not enough positional parameters"),
+            ParamsError::TooMany => write!(f, "too many positional parameters"),
+            ParamsError::Mixed => write!(f, "query mixes named and positional parameters"),
+            ParamsError::ListTooLong { len, max } => {
+                write!(f, "list has {} values, the most allowed is {}", len, max)
+            }
+            ParamsError::TooManyPlaceholders(placeholders) => write!(
+                f,
+                "query has {} placeholders after expanding IN lists, the most allowed is {}",
+                placeholders, MAX_PLACEHOLDERS
+            ),
+            ParamsError::BatchMismatch => write!(
+                f,
+                "every parameter set of a batch must use lists of the same length"
+            ),
+            ParamsError::EmptyNotIn => {
+                write!(f, "an empty list must be the only item of a `NOT IN (...)`")
+            }
+            ParamsError::UnexpectedList => write!(f, "lists cannot be used here"),
+        }
+    }
+}
+
+impl std::error::Error for ParamsError {}
+
+/// Stands in for an empty list: `x IN` it is false and `x NOT IN` it is true, even for `NULL`.
+const EMPTY_LIST: &str = "SELECT NULL FROM DUAL WHERE FALSE";
+
+/// Whether `value` was produced by an [`InList`].
+pub(crate) fn is_list(value: &Value) -> bool {
+    decode(value).is_some()
+}
+
+/// Checks that a parameter set of a batch expanded to the statement prepared for the batch.
+pub(crate) fn check_batch(prepared: &str, expanded: &str) -> Result<()> {
+    if prepared != expanded {
+        return Err(ParamsError::BatchMismatch.into());
+    }
+    Ok(())
+}
+
+/// Whether the innermost parenthesis still open at the end of `before` follows `NOT IN`,
+/// ignoring case and whitespace.
+fn follows_not_in(before: &str) -> bool {
+    let mut depth = 0;
+    let open = before.bytes().rposition(|c| match c {
+        b')' => {
+            depth += 1;
+            false
+        }
+        b'(' if depth > 0 => {
+            depth -= 1;
+            false
+        }
+        b'(' => true,
+        _ => false,
+    });
+    let Some(open) = open else {
+        return false;
+    };
+    let mut words = before[..open].split_ascii_whitespace().rev();
+    matches!(
+        (words.next(), words.next()),
+        (Some(last), Some(previous))
+            if last.eq_ignore_ascii_case("IN") && previous.eq_ignore_ascii_case("NOT")
+    )
+}
+
+pub(crate) struct Placeholder<'a> {
+    start: usize,
+    end: usize,
+    /// `None` for a positional `?`.
+    pub(crate) name: Option<&'a str>,
+}
+
+/// Finds the `?` and `:name` placeholders in `query`, skipping string literals, quoted
+/// identifiers and comments the same way the driver does.
+pub(crate) fn placeholders(query: &str) -> Vec<Placeholder<'_>> {
+    let bytes = query.as_bytes();
+    let skip_to = |from: usize, end: &[u8]| {
+        bytes[from..]
+            .windows(end.len())
+            .position(|window| window == end)
+            .map_or(bytes.len(), |i| from + i + end.len())
+    };
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"' | b'`') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    if bytes[i] == b'\\' && quote != b'`' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            b'#' => i = skip_to(i, b"\n"),
+            b'-' if bytes.get(i + 1) == Some(&b'-')
+                && bytes.get(i + 2).is_none_or(u8::is_ascii_whitespace) =>
+            {
+                i = skip_to(i, b"\n")
+            }
+            // `/*!` and `/*+` hold executable code and optimizer hints.
+            b'/' if bytes.get(i + 1) == Some(&b'*')
+                && !matches!(bytes.get(i + 2), Some(b'!' | b'+')) =>
+            {
+                i = skip_to(i + 2, b"*/")
+            }
+            b'?' => {
+                found.push(Placeholder {
+                    start: i,
+                    end: i + 1,
+                    name: None,
+                });
+                i += 1;
+            }
+            b':' if matches!(bytes.get(i + 1), Some(b'a'..=b'z' | b'_')) => {
+                let end = bytes[i + 1..]
+                    .iter()
+                    .position(|c| !matches!(c, b'a'..=b'z' | b'0'..=b'9' | b'_'))
+                    .map_or(bytes.len(), |len| i + 1 + len);
+                found.push(Placeholder {
+                    start: i,
+                    end,
+                    name: Some(&query[i + 1..end]),
+                });
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+    found
+}
+
+/// Rewrites every placeholder bound to an [`InList`] into one placeholder per element,
+/// returning the new query with positional parameters. Queries without lists pass through.
+///
+/// This is the only way parameters reach the driver, so it also makes sure no list is left
+/// in them to be sent as an opaque string.
+pub(crate) fn expand(
+    query: &str,
+    params: Option<Params>,
+    max_list_len: Option<usize>,
+) -> Result<(Cow<'_, str>, Params)> {
+    let params = params.unwrap_or(Params::Empty);
+    let has_list = match &params {
+        Params::Empty => false,
+        Params::Named(values) => values.values().any(is_list),
+        Params::Positional(values) => values.iter().any(is_list),
+    };
+    if !has_list {
+        return Ok((Cow::Borrowed(query), params));
+    }
+
+    let (sql, values) = positional(query, &params, max_list_len)?;
+    Ok((Cow::Owned(sql), Params::Positional(values)))
+}
+
+/// Rewrites `query` to use only `?` placeholders, expanding [`InList`] values, and returns it
+/// with the values in placeholder order.
+pub(crate) fn positional(
+    query: &str,
+    params: &Params,
+    max_list_len: Option<usize>,
+) -> Result<(String, Vec<Value>)> {
+    if let Params::Empty = params {
+        return Ok((query.to_string(), Vec::new()));
+    }
+    let mut positional = match params {
+        Params::Positional(values) => values.iter(),
+        _ => [].iter(),
+    };
+    let mut sql = String::with_capacity(query.len());
+    let mut values = Vec::new();
+    let mut last = 0;
+    for placeholder in placeholders(query) {
+        let value = match (placeholder.name, params) {
+            (Some(name), Params::Named(named)) => named
+                .get(name.as_bytes())
+                .ok_or_else(|| ParamsError::Missing(name.to_string()))?,
+            (None, Params::Positional(_)) => positional.next().ok_or(ParamsError::NotEnough)?,
+            _ => return Err(ParamsError::Mixed.into()),
+        };
+        sql.push_str(&query[last..placeholder.start]);
+        last = placeholder.end;
+        let list = decode(value);
+        if let Some(list) = &list {
+            if let Some(max) = max_list_len.filter(|max| list.len() > *max) {
+                let len = list.len();
+                return Err(ParamsError::ListTooLong { len, max }.into());
+            }
+            if list.iter().any(is_list) {
+                return Err(ParamsError::UnexpectedList.into());
+            }
+        }
+        match list {
+            Some(list) if list.is_empty() => {
+                let alone = query[placeholder.end..].trim_start().starts_with(')')
+                    && sql.trim_end().ends_with('(');
+                match (alone, follows_not_in(&sql)) {
+                    (true, _) => sql.push_str(EMPTY_LIST),
+                    (false, false) => sql.push_str("NULL"),
+                    (false, true) => return Err(ParamsError::EmptyNotIn.into()),
+                }
+            }
+            Some(list) => {
+                sql.push_str(&vec!["?"; list.len()].join(", "));
+                values.extend(list);
+            }
+            None => {
+                sql.push('?');
+                values.push(value.clone());
+            }
+        }
+    }
+    sql.push_str(&query[last..]);
+
+    if positional.next().is_some() {
+        return Err(ParamsError::TooMany.into());
+    }
+    if values.len() > MAX_PLACEHOLDERS {
+        return Err(ParamsError::TooManyPlaceholders(values.len()).into());
+    }
+    Ok((sql, values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{params, Error};
+
+    #[test]
+    fn test_round_trip() {
+        let values = vec![
+            Value::NULL,
+            Value::from("text"),
+            Value::Int(-1),
+            Value::UInt(u64::MAX),
+            Value::Float(1.5),
+            Value::Double(-2.25),
+            Value::Date(2024, 2, 29, 23, 59, 58, 999_999),
+            Value::Time(true, 1, 2, 3, 4, 5),
+        ];
+        assert_eq!(decode(&InList(values.clone()).into()), Some(values));
+        assert_eq!(decode(&InList(Vec::<i32>::new()).into()), Some(vec![]));
+        assert_eq!(decode(&Value::from("text")), None);
+    }
+
+    #[test]
+    fn test_expand_named() {
+        let params = params! {
+            "ids" => InList(vec![1, 2, 3]),
+            "name" => "alice",
+        };
+        let (query, params) = expand(
+            "SELECT * FROM users WHERE id IN (:ids) AND name = :name",
+            Some(params),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            query,
+            "SELECT * FROM users WHERE id IN (?, ?, ?) AND name = ?"
+        );
+        assert_eq!(
+            params,
+            Params::Positional(vec![
+                Value::Int(1),
+                Value::Int(2),
+                Value::Int(3),
+                Value::from("alice"),
+            ])
+        );
+
+        let params = params! { "ids" => InList(Vec::<u64>::new()) };
+        let (query, params) = expand("SELECT 1 WHERE 1 IN (:ids)", Some(params), None).unwrap();
+        assert_eq!(
+            query,
+            "SELECT 1 WHERE 1 IN (SELECT NULL FROM DUAL WHERE FALSE)"
+        );
+        assert_eq!(params, Params::Positional(vec![]));
+    }
+
+    #[test]
+    fn test_expand_positional() {
+        let params = Params::Positional(vec![InList(vec!["a", "b"]).into(), Value::Int(1)]);
+        let (query, params) = expand("SELECT ? IN (?)", Some(params), None).unwrap();
+        assert_eq!(query, "SELECT ?, ? IN (?)");
+        assert_eq!(
+            params,
+            Params::Positional(vec!["a".into(), "b".into(), 1.into()])
+        );
+    }
+
+    #[test]
+    fn test_expand_skips_literals_and_comments() {
+        let query = "SELECT ':ids', `:ids`, \"it\\\"s :ids\" -- :ids\n/* :ids */ FROM t WHERE a IN (:ids) # :ids";
+        let params = params! { "ids" => InList(vec![1]) };
+        let (expanded, _) = expand(query, Some(params), None).unwrap();
+        assert_eq!(expanded, query.replace("IN (:ids)", "IN (?)"));
+    }
+
+    #[test]
+    fn test_expand_without_lists() {
+        let query = "SELECT :id";
+        let (expanded, params) = expand(query, Some(params! { "id" => 1 }), None).unwrap();
+        assert!(matches!(expanded, Cow::Borrowed(_)));
+        assert!(matches!(params, Params::Named(_)));
+    }
+
+    #[test]
+    fn test_expand_empty_not_in() {
+        let params = || params! { "ids" => InList(Vec::<u64>::new()) };
+        let (query, _) = expand("SELECT 1 WHERE 1 NOT IN ( :ids )", Some(params()), None).unwrap();
+        assert_eq!(
+            query,
+            "SELECT 1 WHERE 1 NOT IN ( SELECT NULL FROM DUAL WHERE FALSE )"
+        );
+        let (query, _) = expand("SELECT 1 WHERE 1 IN (2, :ids)", Some(params()), None).unwrap();
+        assert_eq!(query, "SELECT 1 WHERE 1 IN (2, NULL)");
+        assert!(matches!(
+            expand("SELECT 1 WHERE 1 not\nin (2, :ids)", Some(params()), None),
+            Err(Error::Params(ParamsError::EmptyNotIn))
+        ));
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let list = || InList(vec![1]);
+        let error = |query, params| match expand(query, Some(params), None) {
+            Err(Error::Params(err)) => err,
+            result => panic!("expected a params error, got {:?}", result),
+        };
+        assert_eq!(
+            error("SELECT :ids, :other", params! { "ids" => list() }),
+            ParamsError::Missing("other".to_string())
+        );
+        assert_eq!(
+            error("SELECT ?, :ids", params! { "ids" => list() }),
+            ParamsError::Mixed
+        );
+        let params = Params::Positional(vec![list().into(), Value::Int(1)]);
+        assert_eq!(error("SELECT ?", params), ParamsError::TooMany);
+        let params = Params::Positional(vec![list().into()]);
+        assert_eq!(error("SELECT ?, ?", params), ParamsError::NotEnough);
+        let params = params! { "ids" => InList(vec![Value::from(list())]) };
+        assert_eq!(error("SELECT :ids", params), ParamsError::UnexpectedList);
+
+        let params = params! { "ids" => InList(vec![0; MAX_PLACEHOLDERS + 1]) };
+        assert_eq!(
+            error("SELECT :ids", params),
+            ParamsError::TooManyPlaceholders(MAX_PLACEHOLDERS + 1)
+        );
+        let params = params! { "ids" => InList(vec![1, 2, 3]) };
+        assert!(matches!(
+            expand("SELECT :ids", Some(params), Some(2)),
+            Err(Error::Params(ParamsError::ListTooLong { len: 3, max: 2 }))
+        ));
+    }
+}
@@ -0,0 +1,466 @@
+#[cfg(feature = "async")]
+use bytes::Bytes;
+#[cfg(feature = "async")]
+use futures::future::{self, BoxFuture};
+#[cfg(feature = "async")]
+use futures::{stream, AsyncRead, AsyncReadExt, StreamExt};
+#[cfg(feature = "async")]
+use mysql_async::prelude::GlobalHandler;
+#[cfg(feature = "async")]
+use mysql_async::{InfileData, LocalInfileError};
+#[cfg(feature = "async")]
+use std::cell::Cell;
+#[cfg(feature = "async")]
+use std::collections::BTreeMap;
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::io;
+#[cfg(feature = "async")]
+use std::pin::pin;
+#[cfg(feature = "async")]
+use std::sync::{Arc, Mutex};
+
+use crate::batch::quote_identifier;
+use crate::Value;
+#[cfg(feature = "async")]
+use crate::{Error, Result};
+
+/// How much encoded data is buffered before it is sent to the server.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The format of the data sent by `load_data` and `load_rows`.
+///
+/// The defaults match MySQL's own: tab separated fields, newline terminated lines and
+/// backslash escapes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadDataOptions {
+    field_terminator: String,
+    line_terminator: String,
+    enclosed_by: Option<u8>,
+    escaped_by: Option<u8>,
+    ignore_lines: usize,
+}
+
+impl Default for LoadDataOptions {
+    fn default() -> Self {
+        LoadDataOptions {
+            field_terminator: "\t".to_string(),
+            line_terminator: "\n".to_string(),
+            enclosed_by: None,
+            escaped_by: Some(b'\\'),
+            ignore_lines: 0,
+        }
+    }
+}
+
+impl LoadDataOptions {
+    /// Comma separated fields enclosed in double quotes, with quotes escaped by doubling them.
+    pub fn csv() -> Self {
+        LoadDataOptions {
+            field_terminator: ",".to_string(),
+            enclosed_by: Some(b'"'),
+            escaped_by: None,
+            ..LoadDataOptions::default()
+        }
+    }
+
+    pub fn with_field_terminator(mut self, terminator: &str) -> Self {
+        self.field_terminator = terminator.to_string();
+        self
+    }
+
+    pub fn with_line_terminator(mut self, terminator: &str) -> Self {
+        self.line_terminator = terminator.to_string();
+        self
+    }
+
+    pub fn with_enclosed_by(mut self, enclosed_by: Option<u8>) -> Self {
+        self.enclosed_by = enclosed_by;
+        self
+    }
+
+    pub fn with_escaped_by(mut self, escaped_by: Option<u8>) -> Self {
+        self.escaped_by = escaped_by;
+        self
+    }
+
+    /// Skips the first `lines` lines of the data, e.g. a CSV header.
+    pub fn with_ignore_lines(mut self, lines: usize) -> Self {
+        self.ignore_lines = lines;
+        self
+    }
+
+    pub(crate) fn statement(&self, file_name: &str, table: &str, columns: &[&str]) -> String {
+        let byte = |byte: Option<u8>| Value::Bytes(byte.into_iter().collect()).as_sql(false);
+        let mut statement = format!(
+            "LOAD DATA LOCAL INFILE '{}' INTO TABLE {} FIELDS TERMINATED BY {} ENCLOSED BY {} \
+             ESCAPED BY {} LINES TERMINATED BY {}",
+            file_name,
+            quote_identifier(table),
+            Value::from(&self.field_terminator).as_sql(false),
+            byte(self.enclosed_by),
+            byte(self.escaped_by),
+            Value::from(&self.line_terminator).as_sql(false),
+        );
+        if self.ignore_lines > 0 {
+            statement += &format!(" IGNORE {} LINES", self.ignore_lines);
+        }
+        if !columns.is_empty() {
+            let columns = columns
+                .iter()
+                .map(|column| quote_identifier(column))
+                .collect::<Vec<_>>();
+            statement += &format!(" ({})", columns.join(", "));
+        }
+        statement
+    }
+
+    /// Appends one line for `row` to `out`.
+    pub(crate) fn encode_row(&self, out: &mut Vec<u8>, row: &[Value]) -> io::Result<()> {
+        for (i, value) in row.iter().enumerate() {
+            if i > 0 {
+                out.extend_from_slice(self.field_terminator.as_bytes());
+            }
+            self.encode_value(out, value)?;
+        }
+        out.extend_from_slice(self.line_terminator.as_bytes());
+        Ok(())
+    }
+
+    fn encode_value(&self, out: &mut Vec<u8>, value: &Value) -> io::Result<()> {
+        let text = match value {
+            Value::NULL => {
+                // An unenclosed `NULL` is only read as NULL when fields can be enclosed.
+                return match (self.escaped_by, self.enclosed_by) {
+                    (Some(escape), _) => {
+                        out.extend_from_slice(&[escape, b'N']);
+                        Ok(())
+                    }
+                    (None, Some(_)) => {
+                        out.extend_from_slice(b"NULL");
+                        Ok(())
+                    }
+                    (None, None) => Err(invalid_data(
+                        "NULL cannot be loaded without an escape or enclosing character",
+                    )),
+                };
+            }
+            Value::Bytes(bytes) => bytes.clone(),
+            Value::Int(value) => value.to_string().into_bytes(),
+            Value::UInt(value) => value.to_string().into_bytes(),
+            Value::Float(value) => value.to_string().into_bytes(),
+            Value::Double(value) => value.to_string().into_bytes(),
+            // `as_sql` quotes dates and times, which is not wanted inside the data.
+            value => value.as_sql(false).trim_matches('\'').as_bytes().to_vec(),
+        };
+
+        let special = [
+            self.field_terminator.as_bytes().first(),
+            self.line_terminator.as_bytes().first(),
+        ];
+        out.extend(self.enclosed_by);
+        for byte in text {
+            match (self.escaped_by, self.enclosed_by) {
+                (Some(escape), enclosed_by)
+                    if byte == escape
+                        || Some(byte) == enclosed_by
+                        || special.contains(&Some(&byte)) =>
+                {
+                    out.extend_from_slice(&[escape, byte])
+                }
+                (Some(escape), _) if byte == 0 => out.extend_from_slice(&[escape, b'0']),
+                (None, Some(enclosed_by)) if byte == enclosed_by => {
+                    out.extend_from_slice(&[byte, byte])
+                }
+                (None, None) if special.contains(&Some(&byte)) => return Err(invalid_data(
+                    "a value contains a terminator but there is no escape or enclosing character",
+                )),
+                _ => out.push(byte),
+            }
+        }
+        out.extend(self.enclosed_by);
+        Ok(())
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Streams the contents of `reader` in chunks.
+#[cfg(feature = "async")]
+pub(crate) fn from_reader<R>(reader: R) -> InfileData
+where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    stream::try_unfold(reader, |mut reader| async move {
+        let mut buffer = vec![0; CHUNK_SIZE];
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        buffer.truncate(read);
+        Ok(Some((Bytes::from(buffer), reader)))
+    })
+    .boxed()
+}
+
+/// Encodes `rows` lazily, buffering about [`CHUNK_SIZE`] bytes at a time.
+#[cfg(feature = "async")]
+pub(crate) fn from_rows<I>(rows: I, options: LoadDataOptions) -> InfileData
+where
+    I: IntoIterator<Item = Vec<Value>>,
+    I::IntoIter: Send + 'static,
+{
+    let mut rows = rows.into_iter();
+    stream::iter(std::iter::from_fn(move || {
+        let mut buffer = Vec::with_capacity(CHUNK_SIZE);
+        while buffer.len() < CHUNK_SIZE {
+            let Some(row) = rows.next() else { break };
+            if let Err(err) = options.encode_row(&mut buffer, &row) {
+                return Some(Err(err));
+            }
+        }
+        (!buffer.is_empty()).then(|| Ok(Bytes::from(buffer)))
+    }))
+    .boxed()
+}
+
+/// Encodes `rows` into `writer`, for the blocking driver's local infile handler.
+#[cfg(feature = "blocking")]
+pub(crate) fn write_rows<W, I>(writer: &mut W, rows: I, options: &LoadDataOptions) -> io::Result<()>
+where
+    W: io::Write,
+    I: Iterator<Item = Vec<Value>>,
+{
+    let mut buffer = Vec::with_capacity(CHUNK_SIZE);
+    for row in rows {
+        options.encode_row(&mut buffer, &row)?;
+        if buffer.len() >= CHUNK_SIZE {
+            writer.write_all(&buffer)?;
+            buffer.clear();
+        }
+    }
+    writer.write_all(&buffer)
+}
+
+// Data waiting for the server to request it, keyed by the id of the connection running the
+// statement and the file name used in it.
+#[cfg(feature = "async")]
+static PENDING: Mutex<BTreeMap<(u32, String), InfileData>> = Mutex::new(BTreeMap::new());
+
+#[cfg(feature = "async")]
+thread_local! {
+    // The connection a registered statement is being polled on. The driver calls the handler
+    // from within that poll, which is how the handler knows who is asking.
+    static CONNECTION: Cell<Option<u32>> = const { Cell::new(None) };
+}
+
+/// Data registered for a single `LOAD DATA LOCAL INFILE` statement on one connection. It is
+/// unregistered when dropped, in case the statement failed before the server asked for it.
+///
+/// The file name is a random 128-bit token, and the data is only served while the statement
+/// runs on the connection it was registered for, so a server cannot ask for another
+/// connection's data.
+#[cfg(feature = "async")]
+pub(crate) struct Registration {
+    connection_id: u32,
+    file_name: String,
+}
+
+#[cfg(feature = "async")]
+impl Registration {
+    pub(crate) fn new(connection_id: u32, data: InfileData) -> Result<Self> {
+        let mut token = [0; 16];
+        getrandom::getrandom(&mut token).map_err(|err| Error::Other(Box::new(err)))?;
+        let file_name = token
+            .iter()
+            .fold(String::from("mysql-load-data-"), |name, byte| {
+                name + &format!("{:02x}", byte)
+            });
+        PENDING
+            .lock()
+            .unwrap()
+            .insert((connection_id, file_name.clone()), data);
+        Ok(Registration {
+            connection_id,
+            file_name,
+        })
+    }
+
+    pub(crate) fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    /// Runs `statement`, which must send the registered statement on the registration's
+    /// connection, so that [`Handler`] serves the data to it.
+    pub(crate) async fn run<F: Future>(&self, statement: F) -> F::Output {
+        let mut statement = pin!(statement);
+        future::poll_fn(|cx| {
+            let previous = CONNECTION.replace(Some(self.connection_id));
+            let poll = statement.as_mut().poll(cx);
+            CONNECTION.set(previous);
+            poll
+        })
+        .await
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for Registration {
+    fn drop(&mut self) {
+        PENDING
+            .lock()
+            .unwrap()
+            .remove(&(self.connection_id, std::mem::take(&mut self.file_name)));
+    }
+}
+
+#[cfg(feature = "async")]
+fn take_pending(file_name: &[u8]) -> Option<InfileData> {
+    let connection_id = CONNECTION.get()?;
+    let file_name = std::str::from_utf8(file_name).ok()?.to_string();
+    PENDING.lock().unwrap().remove(&(connection_id, file_name))
+}
+
+/// The pool wide handler serving registered data, falling back to the handler that was
+/// configured on the pool, if any, for other file names.
+///
+/// Only registered data is ever served, so a server cannot use it to read local files.
+#[cfg(feature = "async")]
+pub(crate) struct Handler {
+    fallback: Option<Arc<dyn GlobalHandler>>,
+}
+
+#[cfg(feature = "async")]
+impl Handler {
+    pub(crate) fn new(fallback: Option<Arc<dyn GlobalHandler>>) -> Self {
+        Handler { fallback }
+    }
+}
+
+#[cfg(feature = "async")]
+impl GlobalHandler for Handler {
+    fn handle(&self, file_name: &[u8]) -> BoxFuture<'static, Result<InfileData, LocalInfileError>> {
+        match (take_pending(file_name), &self.fallback) {
+            (Some(data), _) => Box::pin(future::ready(Ok(data))),
+            (None, Some(fallback)) => fallback.handle(file_name),
+            (None, None) => Box::pin(future::ready(Err(
+                LocalInfileError::PathIsNotInTheWhiteList(
+                    String::from_utf8_lossy(file_name).into_owned(),
+                ),
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+
+    fn encode(options: &LoadDataOptions, row: Vec<Value>) -> io::Result<String> {
+        let mut out = Vec::new();
+        options.encode_row(&mut out, &row)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_statement() {
+        let options = LoadDataOptions::csv().with_ignore_lines(1);
+        assert_eq!(
+            options.statement("data", "users", &["id", "name"]),
+            "LOAD DATA LOCAL INFILE 'data' INTO TABLE `users` FIELDS TERMINATED BY ',' \
+             ENCLOSED BY '\\\"' ESCAPED BY '' LINES TERMINATED BY '\\n' IGNORE 1 LINES (`id`, `name`)"
+        );
+        assert_eq!(
+            LoadDataOptions::default().statement("data", "users", &[]),
+            "LOAD DATA LOCAL INFILE 'data' INTO TABLE `users` FIELDS TERMINATED BY '\t' \
+             ENCLOSED BY '' ESCAPED BY '\\\\' LINES TERMINATED BY '\\n'"
+        );
+    }
+
+    #[test]
+    fn test_encode_tsv() {
+        let options = LoadDataOptions::default();
+        let row = vec![
+            Value::from(1),
+            Value::NULL,
+            Value::from("a\tb\\c\nd"),
+            Value::Date(2024, 2, 29, 13, 5, 0, 0),
+        ];
+        assert_eq!(
+            encode(&options, row).unwrap(),
+            "1\t\\N\ta\\\tb\\\\c\\\nd\t2024-02-29 13:05:00\n"
+        );
+    }
+
+    #[test]
+    fn test_encode_csv() {
+        let options = LoadDataOptions::csv();
+        let row = vec![
+            Value::from(1.5),
+            Value::NULL,
+            Value::from("say \"hi\", bob"),
+        ];
+        assert_eq!(
+            encode(&options, row).unwrap(),
+            "\"1.5\",NULL,\"say \"\"hi\"\", bob\"\n"
+        );
+
+        let options = options.with_enclosed_by(None);
+        assert!(encode(&options, vec![Value::NULL]).is_err());
+        assert!(encode(&options, vec![Value::from("a,b")]).is_err());
+    }
+
+    fn data(value: i32) -> InfileData {
+        from_rows(vec![vec![Value::from(value)]], LoadDataOptions::default())
+    }
+
+    /// Asks `handler` for `file_name` the way the driver does, while `registration` runs.
+    async fn request(
+        registration: &Registration,
+        handler: &Handler,
+        file_name: &str,
+    ) -> Result<InfileData, LocalInfileError> {
+        registration
+            .run(async { handler.handle(file_name.as_bytes()).await })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_registration() {
+        let registration = Registration::new(1, data(1)).unwrap();
+        let handler = Handler::new(None);
+        let data = request(&registration, &handler, registration.file_name())
+            .await
+            .unwrap();
+        let chunks = data.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(chunks, [Bytes::from("1\n")]);
+
+        // Each registration is served once, and unknown names are refused.
+        assert!(request(&registration, &handler, registration.file_name())
+            .await
+            .is_err());
+        assert!(request(&registration, &handler, "/etc/passwd")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_registration_is_per_connection() {
+        let first = Registration::new(1, data(1)).unwrap();
+        let second = Registration::new(2, data(2)).unwrap();
+        assert_ne!(first.file_name(), second.file_name());
+        let handler = Handler::new(None);
+
+        // Neither another connection nor code outside a statement can ask for the data.
+        assert!(request(&first, &handler, second.file_name()).await.is_err());
+        assert!(handler.handle(second.file_name().as_bytes()).await.is_err());
+        let data = request(&second, &handler, second.file_name())
+            .await
+            .unwrap();
+        let chunks = data.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(chunks, [Bytes::from("2\n")]);
+    }
+}
@@ -0,0 +1,623 @@
+//! Versioned schema migrations.
+//!
+//! Migrations are `.sql` files named `<version>_<name>.up.sql`, optionally paired with a
+//! `<version>_<name>.down.sql` that undoes them. They are embedded at compile time with
+//! [`embed_migrations!`](crate::embed_migrations) or read at runtime with
+//! [`Migrator::from_dir`]:
+//!
+//! ```ignore
+//! let migrator = mysql::embed_migrations!("migrations");
+//! migrator.run(&database).await?;
+//! ```
+//!
+//! Applied versions are recorded in a `_migrations` table together with a checksum of their
+//! up script, so a file edited after it was applied is reported instead of silently skipped.
+//! A named lock (`GET_LOCK`) serializes migrators running against the same database.
+//!
+//! MySQL commits DDL statements implicitly, so a migration that fails halfway cannot be rolled
+//! back. It is recorded as failed and blocks further runs until the schema is repaired by hand
+//! and its row is deleted from `_migrations`.
+
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use mysql_async::{prelude::*, Conn};
+
+use crate::{rt, Database, Error, Result};
+
+mod file_name;
+
+use file_name::parse_file_name;
+
+const CREATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS _migrations (
+    version BIGINT UNSIGNED NOT NULL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    checksum BINARY(32) NOT NULL,
+    success BOOLEAN NOT NULL,
+    execution_ms BIGINT UNSIGNED NOT NULL DEFAULT 0,
+    applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+)";
+
+const ER_NO_SUCH_TABLE: u16 = 1146;
+
+/// Lock names are limited to 64 characters, so the database name is hashed into it.
+const LOCK_NAME: &str = "CONCAT('_migrations:', SHA1(IFNULL(DATABASE(), '')))";
+
+#[derive(Debug)]
+pub enum MigrateError {
+    /// A `.sql` file in the migrations directory does not follow the naming scheme.
+    InvalidFileName(PathBuf),
+    Read {
+        path: PathBuf,
+        source: io::Error,
+    },
+    DuplicateVersion(u64),
+    /// A down script was found without a matching up script.
+    MissingUp(u64),
+    /// An applied migration's up script no longer matches its recorded checksum.
+    Changed {
+        version: u64,
+        name: String,
+    },
+    /// A migration recorded as applied is not known to this migrator.
+    Missing(u64),
+    /// A migration failed partway through on an earlier run.
+    Dirty(u64),
+    /// A migration has to be undone but has no down script.
+    Irreversible(u64),
+    /// A migration script failed.
+    Failed {
+        version: u64,
+        name: String,
+        source: Box<Error>,
+    },
+    /// `GET_LOCK` failed with an error rather than timing out, e.g. because it was killed.
+    Lock,
+}
+
+impl fmt::Display for MigrateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrateError::InvalidFileName(path) => write!(
+                f,
+                "`{}` is not named `<version>_<name>.up.sql` or `<version>_<name>.down.sql`",
+                path.display()
+            ),
+            MigrateError::Read { path, source } => {
+                write!(f, "failed to read `{}`: {}", path.display(), source)
+            }
+            MigrateError::DuplicateVersion(version) => {
+                write!(f, "migration version {} is used more than once", version)
+            }
+            MigrateError::MissingUp(version) => write!(
+                f,
+                "migration {} has a down script but no up script",
+                version
+            ),
+            MigrateError::Changed { version, name } => write!(
+                f,
+                "migration {} ({}) was edited after it was applied",
+                version, name
+            ),
+            MigrateError::Missing(version) => write!(
+                f,
+                "migration {} was applied but is not in the migrations directory",
+                version
+            ),
+            MigrateError::Dirty(version) => write!(
+                f,
+                "migration {} failed partway through and must be repaired by hand",
+                version
+            ),
+            MigrateError::Irreversible(version) => {
+                write!(f, "migration {} has no down script", version)
+            }
+            MigrateError::Failed {
+                version,
+                name,
+                source,
+            } => write!(f, "migration {} ({}) failed: {}", version, name, source),
+            MigrateError::Lock => write!(f, "failed to acquire the migration lock"),
+        }
+    }
+}
+
+impl std::error::Error for MigrateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MigrateError::Read { source, .. } => Some(source),
+            MigrateError::Failed { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// A single versioned migration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub version: u64,
+    pub name: Cow<'static, str>,
+    pub up: Cow<'static, str>,
+    pub down: Option<Cow<'static, str>>,
+}
+
+impl Migration {
+    pub fn new(
+        version: u64,
+        name: impl Into<Cow<'static, str>>,
+        up: impl Into<Cow<'static, str>>,
+        down: Option<impl Into<Cow<'static, str>>>,
+    ) -> Self {
+        Migration {
+            version,
+            name: name.into(),
+            up: up.into(),
+            down: down.map(Into::into),
+        }
+    }
+
+    /// The SHA-256 of the up script, as recorded in `_migrations`.
+    pub fn checksum(&self) -> [u8; 32] {
+        Sha256::digest(self.up.as_bytes()).into()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    Pending,
+    Applied,
+    /// Applied, but the up script has been edited since.
+    Changed,
+    /// Started, but failed partway through.
+    Failed,
+    /// Applied, but not known to this migrator.
+    Missing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: u64,
+    pub name: String,
+    pub state: MigrationState,
+}
+
+/// A row of `_migrations`.
+struct Applied {
+    version: u64,
+    name: String,
+    checksum: Vec<u8>,
+    success: bool,
+}
+
+/// An ordered set of migrations to apply to a database.
+#[derive(Debug, Clone)]
+pub struct Migrator {
+    migrations: Vec<Migration>,
+    lock_timeout: Option<Duration>,
+}
+
+impl Migrator {
+    pub fn new(mut migrations: Vec<Migration>) -> Result<Self, MigrateError> {
+        migrations.sort_by_key(|migration| migration.version);
+        if let Some(pair) = migrations
+            .windows(2)
+            .find(|pair| pair[0].version == pair[1].version)
+        {
+            return Err(MigrateError::DuplicateVersion(pair[0].version));
+        }
+        Ok(Migrator {
+            migrations,
+            lock_timeout: None,
+        })
+    }
+
+    /// Reads the migrations in `dir` at runtime. Files not ending in `.sql` are ignored.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, MigrateError> {
+        let dir = dir.as_ref();
+        let read_error = |path: &Path| {
+            let path = path.to_path_buf();
+            move |source| MigrateError::Read { path, source }
+        };
+        let mut ups = Vec::new();
+        let mut downs = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(read_error(dir))? {
+            let path = entry.map_err(read_error(dir))?.path();
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if !file_name.ends_with(".sql") {
+                continue;
+            }
+            let (version, name, down) = parse_file_name(file_name)
+                .ok_or_else(|| MigrateError::InvalidFileName(path.clone()))?;
+            let name = name.to_string();
+            let sql = std::fs::read_to_string(&path).map_err(read_error(&path))?;
+            if down {
+                downs.push((version, name, sql));
+            } else {
+                ups.push(Migration::new(version, name, sql, None::<String>));
+            }
+        }
+        for (version, name, sql) in downs {
+            let migration = ups
+                .iter_mut()
+                .find(|migration| migration.version == version)
+                .ok_or(MigrateError::MissingUp(version))?;
+            if migration.name != name || migration.down.is_some() {
+                return Err(MigrateError::DuplicateVersion(version));
+            }
+            migration.down = Some(sql.into());
+        }
+        Migrator::new(ups)
+    }
+
+    /// Waits at most `timeout` for another migrator to finish instead of waiting indefinitely.
+    pub fn with_lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = Some(timeout);
+        self
+    }
+
+    pub fn migrations(&self) -> &[Migration] {
+        &self.migrations
+    }
+
+    /// Lists every known or applied migration with its state, ordered by version.
+    pub async fn status(&self, database: &Database) -> Result<Vec<MigrationStatus>> {
+        rt::compat(async {
+            let mut conn = database.get_conn().await?;
+            Ok(self.statuses(&applied(&mut conn).await?))
+        })
+        .await
+    }
+
+    /// Applies every pending migration in order, returning their versions.
+    pub async fn run(&self, database: &Database) -> Result<Vec<u64>> {
+        rt::compat(async {
+            let mut conn = database.get_conn().await?;
+            self.lock(&mut conn).await?;
+            let result = self.apply_pending(&mut conn).await;
+            unlock(&mut conn, result).await
+        })
+        .await
+    }
+
+    /// Undoes applied migrations newer than `target`, newest first, returning their versions.
+    pub async fn undo(&self, database: &Database, target: u64) -> Result<Vec<u64>> {
+        rt::compat(async {
+            let mut conn = database.get_conn().await?;
+            self.lock(&mut conn).await?;
+            let result = self.undo_to(&mut conn, target).await;
+            unlock(&mut conn, result).await
+        })
+        .await
+    }
+
+    /// Records pending migrations up to and including `version` as applied without running
+    /// them, for databases whose schema predates the migrations. Returns their versions.
+    pub async fn baseline(&self, database: &Database, version: u64) -> Result<Vec<u64>> {
+        rt::compat(async {
+            let mut conn = database.get_conn().await?;
+            self.lock(&mut conn).await?;
+            let result = self.mark_applied(&mut conn, version).await;
+            unlock(&mut conn, result).await
+        })
+        .await
+    }
+
+    fn statuses(&self, applied: &[Applied]) -> Vec<MigrationStatus> {
+        let mut statuses = self
+            .migrations
+            .iter()
+            .map(|migration| {
+                let state = match applied.iter().find(|row| row.version == migration.version) {
+                    None => MigrationState::Pending,
+                    Some(row) if !row.success => MigrationState::Failed,
+                    Some(row) if row.checksum != migration.checksum() => MigrationState::Changed,
+                    Some(_) => MigrationState::Applied,
+                };
+                MigrationStatus {
+                    version: migration.version,
+                    name: migration.name.to_string(),
+                    state,
+                }
+            })
+            .collect::<Vec<_>>();
+        for row in applied {
+            if !self.migrations.iter().any(|m| m.version == row.version) {
+                statuses.push(MigrationStatus {
+                    version: row.version,
+                    name: row.name.clone(),
+                    state: if row.success {
+                        MigrationState::Missing
+                    } else {
+                        MigrationState::Failed
+                    },
+                });
+            }
+        }
+        statuses.sort_by_key(|status| status.version);
+        statuses
+    }
+
+    /// Fails unless every recorded migration is known, unchanged and complete.
+    fn check(&self, applied: &[Applied]) -> Result<Vec<MigrationStatus>> {
+        let statuses = self.statuses(applied);
+        for status in &statuses {
+            let err = match status.state {
+                MigrationState::Pending | MigrationState::Applied => continue,
+                MigrationState::Changed => MigrateError::Changed {
+                    version: status.version,
+                    name: status.name.clone(),
+                },
+                MigrationState::Failed => MigrateError::Dirty(status.version),
+                MigrationState::Missing => MigrateError::Missing(status.version),
+            };
+            return Err(err.into());
+        }
+        Ok(statuses)
+    }
+
+    fn migration(&self, version: u64) -> &Migration {
+        self.migrations
+            .iter()
+            .find(|migration| migration.version == version)
+            .expect("checked migration is known")
+    }
+
+    async fn lock(&self, conn: &mut Conn) -> Result<()> {
+        // GET_LOCK takes fractional seconds, and waits forever for a negative timeout.
+        let timeout = self
+            .lock_timeout
+            .map_or(-1.0, |timeout| timeout.as_secs_f64());
+        let acquired: Option<Option<i64>> = conn
+            .exec_first(format!("SELECT GET_LOCK({}, ?)", LOCK_NAME), (timeout,))
+            .await?;
+        match acquired.flatten() {
+            Some(1) => Ok(()),
+            Some(0) => Err(Error::Timeout(self.lock_timeout.unwrap_or_default())),
+            _ => Err(MigrateError::Lock.into()),
+        }
+    }
+
+    async fn apply_pending(&self, conn: &mut Conn) -> Result<Vec<u64>> {
+        conn.query_drop(CREATE_TABLE).await?;
+        let statuses = self.check(&applied(conn).await?)?;
+        let mut versions = Vec::new();
+        for status in statuses {
+            if status.state != MigrationState::Pending {
+                continue;
+            }
+            let migration = self.migration(status.version);
+            record(conn, migration, false).await?;
+            let started = Instant::now();
+            conn.query_drop(migration.up.as_ref())
+                .await
+                .map_err(|err| failed(migration, err.into()))?;
+            conn.exec_drop(
+                "UPDATE _migrations SET success = TRUE, execution_ms = ? WHERE version = ?",
+                (started.elapsed().as_millis() as u64, migration.version),
+            )
+            .await?;
+            versions.push(migration.version);
+        }
+        Ok(versions)
+    }
+
+    async fn mark_applied(&self, conn: &mut Conn, version: u64) -> Result<Vec<u64>> {
+        conn.query_drop(CREATE_TABLE).await?;
+        let statuses = self.check(&applied(conn).await?)?;
+        let mut versions = Vec::new();
+        for status in statuses {
+            if status.state != MigrationState::Pending || status.version > version {
+                continue;
+            }
+            let migration = self.migration(status.version);
+            record(conn, migration, true).await?;
+            versions.push(migration.version);
+        }
+        Ok(versions)
+    }
+
+    async fn undo_to(&self, conn: &mut Conn, target: u64) -> Result<Vec<u64>> {
+        conn.query_drop(CREATE_TABLE).await?;
+        let statuses = self.check(&applied(conn).await?)?;
+        let migrations = statuses
+            .iter()
+            .rev()
+            .filter(|status| status.state == MigrationState::Applied && status.version > target)
+            .map(|status| self.migration(status.version))
+            .collect::<Vec<_>>();
+        if let Some(migration) = migrations.iter().find(|m| m.down.is_none()) {
+            return Err(MigrateError::Irreversible(migration.version).into());
+        }
+        let mut versions = Vec::new();
+        for migration in migrations {
+            let down = migration.down.as_deref().expect("checked down script");
+            conn.exec_drop(
+                "UPDATE _migrations SET success = FALSE WHERE version = ?",
+                (migration.version,),
+            )
+            .await?;
+            conn.query_drop(down)
+                .await
+                .map_err(|err| failed(migration, err.into()))?;
+            conn.exec_drop(
+                "DELETE FROM _migrations WHERE version = ?",
+                (migration.version,),
+            )
+            .await?;
+            versions.push(migration.version);
+        }
+        Ok(versions)
+    }
+}
+
+fn failed(migration: &Migration, err: Error) -> Error {
+    MigrateError::Failed {
+        version: migration.version,
+        name: migration.name.to_string(),
+        source: Box::new(err),
+    }
+    .into()
+}
+
+async fn record(conn: &mut Conn, migration: &Migration, success: bool) -> Result<()> {
+    conn.exec_drop(
+        "INSERT INTO _migrations (version, name, checksum, success) VALUES (?, ?, ?, ?)",
+        (
+            migration.version,
+            migration.name.as_ref(),
+            migration.checksum().to_vec(),
+            success,
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Releases the migration lock, keeping the first error.
+async fn unlock<T>(conn: &mut Conn, result: Result<T>) -> Result<T> {
+    let released = conn
+        .query_drop(format!("DO RELEASE_LOCK({})", LOCK_NAME))
+        .await;
+    let value = result?;
+    released?;
+    Ok(value)
+}
+
+/// Reads `_migrations`, treating a missing table as no migrations applied.
+async fn applied(conn: &mut Conn) -> Result<Vec<Applied>> {
+    let rows: Vec<(u64, String, Vec<u8>, bool)> = match conn
+        .query("SELECT version, name, checksum, success FROM _migrations ORDER BY version")
+        .await
+        .map_err(Error::from)
+    {
+        Err(err) if err.code() == Some(ER_NO_SUCH_TABLE) => Vec::new(),
+        rows => rows?,
+    };
+    Ok(rows
+        .into_iter()
+        .map(|(version, name, checksum, success)| Applied {
+            version,
+            name,
+            checksum,
+            success,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migrator() -> Migrator {
+        Migrator::new(vec![
+            Migration::new(2, "second", "SELECT 2", Some("SELECT -2")),
+            Migration::new(1, "first", "SELECT 1", None::<&str>),
+        ])
+        .unwrap()
+    }
+
+    fn applied(migration: &Migration, success: bool) -> Applied {
+        Applied {
+            version: migration.version,
+            name: migration.name.to_string(),
+            checksum: migration.checksum().to_vec(),
+            success,
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let versions = migrator()
+            .migrations()
+            .iter()
+            .map(|migration| migration.version)
+            .collect::<Vec<_>>();
+        assert_eq!(versions, [1, 2]);
+
+        let duplicate = Migrator::new(vec![
+            Migration::new(1, "a", "", None::<&str>),
+            Migration::new(1, "b", "", None::<&str>),
+        ]);
+        assert!(matches!(duplicate, Err(MigrateError::DuplicateVersion(1))));
+    }
+
+    #[test]
+    fn test_from_dir() {
+        let dir = std::env::temp_dir().join(format!("mysql-migrate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("1_init.up.sql"), "CREATE TABLE t (id INT)").unwrap();
+        std::fs::write(dir.join("1_init.down.sql"), "DROP TABLE t").unwrap();
+        std::fs::write(dir.join("README.md"), "ignored").unwrap();
+        let migrator = Migrator::from_dir(&dir).unwrap();
+        assert_eq!(
+            migrator.migrations(),
+            [Migration::new(
+                1,
+                "init",
+                "CREATE TABLE t (id INT)",
+                Some("DROP TABLE t")
+            )]
+        );
+
+        std::fs::write(dir.join("2_orphan.down.sql"), "").unwrap();
+        assert!(matches!(
+            Migrator::from_dir(&dir),
+            Err(MigrateError::MissingUp(2))
+        ));
+        std::fs::write(dir.join("bad.sql"), "").unwrap();
+        assert!(matches!(
+            Migrator::from_dir(&dir),
+            Err(MigrateError::InvalidFileName(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_statuses() {
+        let migrator = migrator();
+        let [first, second] = migrator.migrations() else {
+            unreachable!()
+        };
+        let states = |applied: &[Applied]| {
+            migrator
+                .statuses(applied)
+                .into_iter()
+                .map(|status| (status.version, status.state))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            states(&[applied(first, true)]),
+            [(1, MigrationState::Applied), (2, MigrationState::Pending)]
+        );
+        assert!(migrator.check(&[applied(first, true)]).is_ok());
+
+        let mut edited = applied(second, true);
+        edited.checksum = vec![0; 32];
+        assert_eq!(
+            states(&[applied(first, true), edited]),
+            [(1, MigrationState::Applied), (2, MigrationState::Changed)]
+        );
+
+        let mut unknown = applied(first, true);
+        unknown.version = 3;
+        assert_eq!(
+            states(&[applied(first, false), unknown]),
+            [
+                (1, MigrationState::Failed),
+                (2, MigrationState::Pending),
+                (3, MigrationState::Missing)
+            ]
+        );
+        assert!(matches!(
+            migrator.check(&[applied(first, false)]),
+            Err(Error::Migrate(MigrateError::Dirty(1)))
+        ));
+    }
+}
@@ -0,0 +1,36 @@
+//! Migration file names, shared with `embed_migrations!`: the proc macro crate cannot depend on
+//! this one, so it compiles this file in as its own module.
+
+/// Splits `<version>_<name>.up.sql`, `<version>_<name>.down.sql` or `<version>_<name>.sql`
+/// into its version, name and whether it is a down migration.
+pub(crate) fn parse_file_name(file_name: &str) -> Option<(u64, &str, bool)> {
+    let stem = file_name.strip_suffix(".sql")?;
+    let (stem, down) = match stem.strip_suffix(".down") {
+        Some(stem) => (stem, true),
+        None => (stem.strip_suffix(".up").unwrap_or(stem), false),
+    };
+    let (version, name) = stem.split_once('_')?;
+    Some((version.parse().ok()?, name, down))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_file_name() {
+        assert_eq!(
+            parse_file_name("20240101_create_users.up.sql"),
+            Some((20240101, "create_users", false))
+        );
+        assert_eq!(
+            parse_file_name("1_create_users.down.sql"),
+            Some((1, "create_users", true))
+        );
+        assert_eq!(parse_file_name("1_init.sql"), Some((1, "init", false)));
+        assert_eq!(parse_file_name("create_users.up.sql"), None);
+        assert_eq!(parse_file_name("1.up.sql"), None);
+        assert_eq!(parse_file_name("x_init.up.sql"), None);
+        assert_eq!(parse_file_name("1_init.up.txt"), None);
+    }
+}
@@ -0,0 +1,865 @@
+//! Builders for `SELECT`, `INSERT`, `UPDATE` and `DELETE` statements.
+//!
+//! Identifiers are quoted with backticks and every value is sent as a positional parameter,
+//! so values are never part of the SQL text. [`Select::build`] and friends return the query
+//! and its parameters, ready for [`Database::select`](crate::Database::select) or
+//! [`Database::execute`](crate::Database::execute):
+//!
+//! ```ignore
+//! use mysql::query::{col, Select};
+//!
+//! let (query, params) = Select::from("users")
+//!     .columns(&["id", "name"])
+//!     .filter(col("active").eq(true).and(col("age").ge(18).or(col("admin").eq(true))))
+//!     .order_by_desc("created_at")
+//!     .limit(20)
+//!     .build();
+//! let users: Vec<(u64, String)> = db.select(&query, Some(params)).await?;
+//! ```
+
+use std::fmt;
+
+use crate::batch::quote_identifier;
+use crate::{Params, Result, Value};
+
+/// Why an [`Insert`] or [`Update`] could not be built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    /// An `INSERT` has no rows.
+    NoRows { table: String },
+    /// A row of an `INSERT` has a different number of values than it has columns.
+    RowLength {
+        row: usize,
+        values: usize,
+        columns: usize,
+    },
+    /// An `UPDATE` sets no columns.
+    NoAssignments { table: String },
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::NoRows { table } => write!(f, "INSERT into `{}` has no rows", table),
+            QueryError::RowLength {
+                row,
+                values,
+                columns,
+            } => write!(
+                f,
+                "INSERT row {} has {} values for {} columns",
+                row, values, columns
+            ),
+            QueryError::NoAssignments { table } => {
+                write!(f, "UPDATE of `{}` sets no columns", table)
+            }
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Refers to a column, e.g. `col("name")` or `col("users.name")`, to build a [`Condition`].
+pub fn col(name: &str) -> Column {
+    Column(name.to_string())
+}
+
+/// A condition that holds if all of `conditions` hold, or always if there are none.
+pub fn all(conditions: impl IntoIterator<Item = Condition>) -> Condition {
+    Condition(Expr::All(conditions.into_iter().collect()))
+}
+
+/// A condition that holds if any of `conditions` holds, or never if there are none.
+pub fn any(conditions: impl IntoIterator<Item = Condition>) -> Condition {
+    Condition(Expr::Any(conditions.into_iter().collect()))
+}
+
+#[derive(Debug, Clone)]
+pub struct Column(String);
+
+impl Column {
+    pub fn eq(self, value: impl Into<Value>) -> Condition {
+        self.compare("=", value)
+    }
+
+    pub fn ne(self, value: impl Into<Value>) -> Condition {
+        self.compare("<>", value)
+    }
+
+    pub fn lt(self, value: impl Into<Value>) -> Condition {
+        self.compare("<", value)
+    }
+
+    pub fn le(self, value: impl Into<Value>) -> Condition {
+        self.compare("<=", value)
+    }
+
+    pub fn gt(self, value: impl Into<Value>) -> Condition {
+        self.compare(">", value)
+    }
+
+    pub fn ge(self, value: impl Into<Value>) -> Condition {
+        self.compare(">=", value)
+    }
+
+    pub fn like(self, pattern: impl Into<Value>) -> Condition {
+        self.compare("LIKE", pattern)
+    }
+
+    /// Compares with another column, e.g. for the `ON` clause of a join.
+    pub fn eq_col(self, other: &str) -> Condition {
+        Condition(Expr::Columns {
+            left: self.0,
+            op: "=",
+            right: other.to_string(),
+        })
+    }
+
+    pub fn is_null(self) -> Condition {
+        Condition(Expr::Null {
+            column: self.0,
+            negated: false,
+        })
+    }
+
+    pub fn is_not_null(self) -> Condition {
+        Condition(Expr::Null {
+            column: self.0,
+            negated: true,
+        })
+    }
+
+    /// `column IN (...)`, which never holds for an empty list.
+    pub fn in_list<V: Into<Value>>(self, values: impl IntoIterator<Item = V>) -> Condition {
+        Condition(Expr::In {
+            column: self.0,
+            values: values.into_iter().map(Into::into).collect(),
+            negated: false,
+        })
+    }
+
+    /// `column NOT IN (...)`, which always holds for an empty list.
+    pub fn not_in<V: Into<Value>>(self, values: impl IntoIterator<Item = V>) -> Condition {
+        Condition(Expr::In {
+            column: self.0,
+            values: values.into_iter().map(Into::into).collect(),
+            negated: true,
+        })
+    }
+
+    pub fn between(self, low: impl Into<Value>, high: impl Into<Value>) -> Condition {
+        Condition(Expr::Between {
+            column: self.0,
+            low: low.into(),
+            high: high.into(),
+        })
+    }
+
+    fn compare(self, op: &'static str, value: impl Into<Value>) -> Condition {
+        Condition(Expr::Compare {
+            column: self.0,
+            op,
+            value: value.into(),
+        })
+    }
+}
+
+/// A boolean expression for `WHERE` and `ON` clauses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition(Expr);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Compare {
+        column: String,
+        op: &'static str,
+        value: Value,
+    },
+    Columns {
+        left: String,
+        op: &'static str,
+        right: String,
+    },
+    Null {
+        column: String,
+        negated: bool,
+    },
+    In {
+        column: String,
+        values: Vec<Value>,
+        negated: bool,
+    },
+    Between {
+        column: String,
+        low: Value,
+        high: Value,
+    },
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    pub fn and(self, other: Condition) -> Condition {
+        match self.0 {
+            Expr::All(mut conditions) => {
+                conditions.push(other);
+                Condition(Expr::All(conditions))
+            }
+            expr => Condition(Expr::All(vec![Condition(expr), other])),
+        }
+    }
+
+    pub fn or(self, other: Condition) -> Condition {
+        match self.0 {
+            Expr::Any(mut conditions) => {
+                conditions.push(other);
+                Condition(Expr::Any(conditions))
+            }
+            expr => Condition(Expr::Any(vec![Condition(expr), other])),
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Condition {
+        Condition(Expr::Not(Box::new(self)))
+    }
+
+    fn write(&self, sql: &mut String, params: &mut Vec<Value>) {
+        match &self.0 {
+            Expr::Compare { column, op, value } => {
+                *sql += &format!("{} {} ?", quote_identifier(column), op);
+                params.push(value.clone());
+            }
+            Expr::Columns { left, op, right } => {
+                *sql += &format!(
+                    "{} {} {}",
+                    quote_identifier(left),
+                    op,
+                    quote_identifier(right)
+                );
+            }
+            Expr::Null { column, negated } => {
+                let not = if *negated { " NOT" } else { "" };
+                *sql += &format!("{} IS{} NULL", quote_identifier(column), not);
+            }
+            Expr::In {
+                values, negated, ..
+            } if values.is_empty() => *sql += if *negated { "TRUE" } else { "FALSE" },
+            Expr::In {
+                column,
+                values,
+                negated,
+            } => {
+                let not = if *negated { " NOT" } else { "" };
+                let placeholders = vec!["?"; values.len()].join(", ");
+                *sql += &format!("{}{} IN ({})", quote_identifier(column), not, placeholders);
+                params.extend(values.iter().cloned());
+            }
+            Expr::Between { column, low, high } => {
+                *sql += &format!("{} BETWEEN ? AND ?", quote_identifier(column));
+                params.extend([low.clone(), high.clone()]);
+            }
+            Expr::All(conditions) => write_group(sql, params, conditions, " AND ", "TRUE"),
+            Expr::Any(conditions) => write_group(sql, params, conditions, " OR ", "FALSE"),
+            Expr::Not(condition) => {
+                *sql += "NOT (";
+                condition.write(sql, params);
+                *sql += ")";
+            }
+        }
+    }
+}
+
+fn write_group(
+    sql: &mut String,
+    params: &mut Vec<Value>,
+    conditions: &[Condition],
+    separator: &str,
+    empty: &str,
+) {
+    match conditions {
+        [] => *sql += empty,
+        [condition] => condition.write(sql, params),
+        conditions => {
+            *sql += "(";
+            for (i, condition) in conditions.iter().enumerate() {
+                if i > 0 {
+                    *sql += separator;
+                }
+                condition.write(sql, params);
+            }
+            *sql += ")";
+        }
+    }
+}
+
+/// The `WHERE`, `ORDER BY` and `LIMIT` clauses shared by the builders.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Clauses {
+    filter: Option<Condition>,
+    order_by: Vec<(String, bool)>,
+    limit: Option<u64>,
+    offset: Option<u64>,
+}
+
+impl Clauses {
+    fn filter(&mut self, condition: Condition) {
+        self.filter = Some(match self.filter.take() {
+            Some(filter) => filter.and(condition),
+            None => condition,
+        });
+    }
+
+    fn write_where(&self, sql: &mut String, params: &mut Vec<Value>) {
+        if let Some(filter) = &self.filter {
+            *sql += " WHERE ";
+            filter.write(sql, params);
+        }
+    }
+
+    fn write_order_and_limit(&self, sql: &mut String) {
+        if !self.order_by.is_empty() {
+            let order_by = self
+                .order_by
+                .iter()
+                .map(|(column, descending)| {
+                    let direction = if *descending { "DESC" } else { "ASC" };
+                    format!("{} {}", quote_identifier(column), direction)
+                })
+                .collect::<Vec<_>>();
+            *sql += &format!(" ORDER BY {}", order_by.join(", "));
+        }
+        if let Some(limit) = self.limit {
+            *sql += &format!(" LIMIT {}", limit);
+        }
+        if let Some(offset) = self.offset {
+            if self.limit.is_none() {
+                // MySQL has no OFFSET without LIMIT, so use the largest possible limit.
+                *sql += &format!(" LIMIT {}", u64::MAX);
+            }
+            *sql += &format!(" OFFSET {}", offset);
+        }
+    }
+}
+
+fn params(values: Vec<Value>) -> Params {
+    if values.is_empty() {
+        Params::Empty
+    } else {
+        Params::Positional(values)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum JoinKind {
+    Inner,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    table: String,
+    distinct: bool,
+    columns: Vec<String>,
+    joins: Vec<(JoinKind, String, Condition)>,
+    group_by: Vec<String>,
+    clauses: Clauses,
+}
+
+impl Select {
+    pub fn from(table: &str) -> Self {
+        Select {
+            table: table.to_string(),
+            distinct: false,
+            columns: Vec::new(),
+            joins: Vec::new(),
+            group_by: Vec::new(),
+            clauses: Clauses::default(),
+        }
+    }
+
+    /// Selects these columns instead of `*`.
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns
+            .extend(columns.iter().map(|column| quote_identifier(column)));
+        self
+    }
+
+    /// Selects a raw SQL expression such as `COUNT(*) AS total`.
+    ///
+    /// The expression is not quoted or escaped, so it must not contain user input.
+    pub fn expr(mut self, expr: &str) -> Self {
+        self.columns.push(expr.to_string());
+        self
+    }
+
+    pub fn distinct(mut self) -> Self {
+        self.distinct = true;
+        self
+    }
+
+    pub fn join(mut self, table: &str, on: Condition) -> Self {
+        self.joins.push((JoinKind::Inner, table.to_string(), on));
+        self
+    }
+
+    pub fn left_join(mut self, table: &str, on: Condition) -> Self {
+        self.joins.push((JoinKind::Left, table.to_string(), on));
+        self
+    }
+
+    pub fn right_join(mut self, table: &str, on: Condition) -> Self {
+        self.joins.push((JoinKind::Right, table.to_string(), on));
+        self
+    }
+
+    /// Adds a `WHERE` condition, combined with `AND` if called several times.
+    pub fn filter(mut self, condition: Condition) -> Self {
+        self.clauses.filter(condition);
+        self
+    }
+
+    pub fn group_by(mut self, column: &str) -> Self {
+        self.group_by.push(column.to_string());
+        self
+    }
+
+    pub fn order_by(mut self, column: &str) -> Self {
+        self.clauses.order_by.push((column.to_string(), false));
+        self
+    }
+
+    pub fn order_by_desc(mut self, column: &str) -> Self {
+        self.clauses.order_by.push((column.to_string(), true));
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.clauses.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.clauses.offset = Some(offset);
+        self
+    }
+
+    pub fn build(&self) -> (String, Params) {
+        let mut values = Vec::new();
+        let mut sql = String::from("SELECT ");
+        if self.distinct {
+            sql += "DISTINCT ";
+        }
+        if self.columns.is_empty() {
+            sql += "*";
+        } else {
+            sql += &self.columns.join(", ");
+        }
+        sql += &format!(" FROM {}", quote_identifier(&self.table));
+        for (kind, table, on) in &self.joins {
+            sql += match kind {
+                JoinKind::Inner => " JOIN ",
+                JoinKind::Left => " LEFT JOIN ",
+                JoinKind::Right => " RIGHT JOIN ",
+            };
+            sql += &quote_identifier(table);
+            sql += " ON ";
+            on.write(&mut sql, &mut values);
+        }
+        self.clauses.write_where(&mut sql, &mut values);
+        if !self.group_by.is_empty() {
+            let group_by = self
+                .group_by
+                .iter()
+                .map(|column| quote_identifier(column))
+                .collect::<Vec<_>>();
+            sql += &format!(" GROUP BY {}", group_by.join(", "));
+        }
+        self.clauses.write_order_and_limit(&mut sql);
+        (sql, params(values))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Insert {
+    table: String,
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+    ignore: bool,
+    on_duplicate: Vec<(String, Option<Value>)>,
+}
+
+impl Insert {
+    pub fn into(table: &str, columns: &[&str]) -> Self {
+        Insert {
+            table: table.to_string(),
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            rows: Vec::new(),
+            ignore: false,
+            on_duplicate: Vec::new(),
+        }
+    }
+
+    /// Adds a row of values, one for each column.
+    pub fn values(mut self, row: Vec<Value>) -> Self {
+        self.rows.push(row);
+        self
+    }
+
+    /// `INSERT IGNORE`, skipping rows that would violate a unique key.
+    pub fn ignore(mut self) -> Self {
+        self.ignore = true;
+        self
+    }
+
+    /// On a duplicate key, updates these columns with the values from the rejected row.
+    pub fn on_duplicate_key_update(mut self, columns: &[&str]) -> Self {
+        self.on_duplicate
+            .extend(columns.iter().map(|column| (column.to_string(), None)));
+        self
+    }
+
+    /// On a duplicate key, sets `column` to `value`.
+    pub fn on_duplicate_key_set(mut self, column: &str, value: impl Into<Value>) -> Self {
+        self.on_duplicate
+            .push((column.to_string(), Some(value.into())));
+        self
+    }
+
+    /// Fails if no rows were added with [`Insert::values`], or if a row does not have one
+    /// value for each column.
+    pub fn build(&self) -> Result<(String, Params)> {
+        if self.rows.is_empty() {
+            return Err(QueryError::NoRows {
+                table: self.table.clone(),
+            }
+            .into());
+        }
+        if let Some((row, values)) = self
+            .rows
+            .iter()
+            .enumerate()
+            .find(|(_, values)| values.len() != self.columns.len())
+        {
+            return Err(QueryError::RowLength {
+                row,
+                values: values.len(),
+                columns: self.columns.len(),
+            }
+            .into());
+        }
+        let mut values = Vec::new();
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| quote_identifier(column))
+            .collect::<Vec<_>>();
+        let row = format!("({})", vec!["?"; columns.len()].join(", "));
+        let mut sql = format!(
+            "INSERT {}INTO {} ({}) VALUES {}",
+            if self.ignore { "IGNORE " } else { "" },
+            quote_identifier(&self.table),
+            columns.join(", "),
+            vec![row.as_str(); self.rows.len()].join(", ")
+        );
+        values.extend(self.rows.iter().flatten().cloned());
+        if !self.on_duplicate.is_empty() {
+            let assignments = self
+                .on_duplicate
+                .iter()
+                .map(|(column, value)| {
+                    let column = quote_identifier(column);
+                    match value {
+                        Some(value) => {
+                            values.push(value.clone());
+                            format!("{} = ?", column)
+                        }
+                        None => format!("{} = VALUES({})", column, column),
+                    }
+                })
+                .collect::<Vec<_>>();
+            sql += &format!(" ON DUPLICATE KEY UPDATE {}", assignments.join(", "));
+        }
+        Ok((sql, params(values)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Update {
+    table: String,
+    set: Vec<(String, Value)>,
+    clauses: Clauses,
+}
+
+impl Update {
+    pub fn table(table: &str) -> Self {
+        Update {
+            table: table.to_string(),
+            set: Vec::new(),
+            clauses: Clauses::default(),
+        }
+    }
+
+    pub fn set(mut self, column: &str, value: impl Into<Value>) -> Self {
+        self.set.push((column.to_string(), value.into()));
+        self
+    }
+
+    /// Adds a `WHERE` condition, combined with `AND` if called several times.
+    pub fn filter(mut self, condition: Condition) -> Self {
+        self.clauses.filter(condition);
+        self
+    }
+
+    pub fn order_by(mut self, column: &str) -> Self {
+        self.clauses.order_by.push((column.to_string(), false));
+        self
+    }
+
+    pub fn order_by_desc(mut self, column: &str) -> Self {
+        self.clauses.order_by.push((column.to_string(), true));
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.clauses.limit = Some(limit);
+        self
+    }
+
+    /// Fails if no columns were set with [`Update::set`].
+    pub fn build(&self) -> Result<(String, Params)> {
+        if self.set.is_empty() {
+            return Err(QueryError::NoAssignments {
+                table: self.table.clone(),
+            }
+            .into());
+        }
+        let mut values = Vec::new();
+        let assignments = self
+            .set
+            .iter()
+            .map(|(column, value)| {
+                values.push(value.clone());
+                format!("{} = ?", quote_identifier(column))
+            })
+            .collect::<Vec<_>>();
+        let mut sql = format!(
+            "UPDATE {} SET {}",
+            quote_identifier(&self.table),
+            assignments.join(", ")
+        );
+        self.clauses.write_where(&mut sql, &mut values);
+        self.clauses.write_order_and_limit(&mut sql);
+        Ok((sql, params(values)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delete {
+    table: String,
+    clauses: Clauses,
+}
+
+impl Delete {
+    pub fn from(table: &str) -> Self {
+        Delete {
+            table: table.to_string(),
+            clauses: Clauses::default(),
+        }
+    }
+
+    /// Adds a `WHERE` condition, combined with `AND` if called several times.
+    pub fn filter(mut self, condition: Condition) -> Self {
+        self.clauses.filter(condition);
+        self
+    }
+
+    pub fn order_by(mut self, column: &str) -> Self {
+        self.clauses.order_by.push((column.to_string(), false));
+        self
+    }
+
+    pub fn order_by_desc(mut self, column: &str) -> Self {
+        self.clauses.order_by.push((column.to_string(), true));
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.clauses.limit = Some(limit);
+        self
+    }
+
+    pub fn build(&self) -> (String, Params) {
+        let mut values = Vec::new();
+        let mut sql = format!("DELETE FROM {}", quote_identifier(&self.table));
+        self.clauses.write_where(&mut sql, &mut values);
+        self.clauses.write_order_and_limit(&mut sql);
+        (sql, params(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    fn positional(params: Params) -> Vec<Value> {
+        match params {
+            Params::Positional(values) => values,
+            Params::Empty => Vec::new(),
+            Params::Named(_) => panic!("expected positional params"),
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let (sql, params) = Select::from("users")
+            .columns(&["users.id", "name"])
+            .expr("COUNT(*) AS total")
+            .left_join("orders", col("orders.user_id").eq_col("users.id"))
+            .filter(col("active").eq(true))
+            .filter(
+                col("age")
+                    .ge(18)
+                    .or(col("role").in_list(["admin", "staff"])),
+            )
+            .group_by("users.id")
+            .order_by_desc("name")
+            .limit(10)
+            .offset(20)
+            .build();
+        assert_eq!(
+            sql,
+            "SELECT `users`.`id`, `name`, COUNT(*) AS total FROM `users` \
+             LEFT JOIN `orders` ON `orders`.`user_id` = `users`.`id` \
+             WHERE (`active` = ? AND (`age` >= ? OR `role` IN (?, ?))) \
+             GROUP BY `users`.`id` ORDER BY `name` DESC LIMIT 10 OFFSET 20"
+        );
+        assert_eq!(
+            positional(params),
+            [
+                Value::from(true),
+                Value::from(18),
+                Value::from("admin"),
+                Value::from("staff")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_select_defaults() {
+        let (sql, params) = Select::from("users").build();
+        assert_eq!(sql, "SELECT * FROM `users`");
+        assert_eq!(params, Params::Empty);
+    }
+
+    #[test]
+    fn test_conditions() {
+        let condition = all([
+            col("a").in_list(Vec::<i32>::new()),
+            col("b").not_in(Vec::<i32>::new()),
+            any([]),
+            col("c").is_null().not(),
+            col("d").between(1, 2),
+        ]);
+        let mut sql = String::new();
+        let mut values = Vec::new();
+        condition.write(&mut sql, &mut values);
+        assert_eq!(
+            sql,
+            "(FALSE AND TRUE AND FALSE AND NOT (`c` IS NULL) AND `d` BETWEEN ? AND ?)"
+        );
+        assert_eq!(values, [Value::from(1), Value::from(2)]);
+    }
+
+    #[test]
+    fn test_no_injection() {
+        let (sql, params) = Select::from("users`; DROP TABLE users; --")
+            .filter(col("name").eq("'; DROP TABLE users; --"))
+            .build();
+        assert_eq!(
+            sql,
+            "SELECT * FROM `users``; DROP TABLE users; --` WHERE `name` = ?"
+        );
+        assert_eq!(positional(params), [Value::from("'; DROP TABLE users; --")]);
+    }
+
+    #[test]
+    fn test_insert() {
+        let (sql, params) = Insert::into("users", &["id", "name"])
+            .values(vec![1.into(), "a".into()])
+            .values(vec![2.into(), "b".into()])
+            .on_duplicate_key_update(&["name"])
+            .on_duplicate_key_set("updates", 1)
+            .build()
+            .unwrap();
+        assert_eq!(
+            sql,
+            "INSERT INTO `users` (`id`, `name`) VALUES (?, ?), (?, ?) \
+             ON DUPLICATE KEY UPDATE `name` = VALUES(`name`), `updates` = ?"
+        );
+        assert_eq!(positional(params).len(), 5);
+    }
+
+    #[test]
+    fn test_insert_row_length() {
+        let err = Insert::into("users", &["id", "name"])
+            .values(vec![1.into(), "a".into()])
+            .values(vec![2.into()])
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Query(QueryError::RowLength {
+                row: 1,
+                values: 1,
+                columns: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_insert_without_rows() {
+        let err = Insert::into("users", &["id", "name"])
+            .on_duplicate_key_update(&["name"])
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Error::Query(QueryError::NoRows { table }) if table == "users"));
+    }
+
+    #[test]
+    fn test_update_without_set() {
+        let err = Update::table("users")
+            .filter(col("id").eq(1))
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Query(QueryError::NoAssignments { table }) if table == "users"
+        ));
+    }
+
+    #[test]
+    fn test_update_and_delete() {
+        let (sql, params) = Update::table("users")
+            .set("name", "a")
+            .filter(col("id").eq(1))
+            .limit(1)
+            .build()
+            .unwrap();
+        assert_eq!(sql, "UPDATE `users` SET `name` = ? WHERE `id` = ? LIMIT 1");
+        assert_eq!(positional(params), [Value::from("a"), Value::from(1)]);
+
+        let (sql, _) = Delete::from("sessions")
+            .filter(col("expires_at").lt("2024-01-01"))
+            .order_by("expires_at")
+            .limit(100)
+            .build();
+        assert_eq!(
+            sql,
+            "DELETE FROM `sessions` WHERE `expires_at` < ? ORDER BY `expires_at` ASC LIMIT 100"
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{Error, Result};
+
+/// Metadata reported by the server for a statement that does not return rows.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecResult {
+    pub affected_rows: u64,
+    /// The `AUTO_INCREMENT` value generated by the statement, if any.
+    pub last_insert_id: Option<u64>,
+    pub warnings: u16,
+    /// The human readable info string, e.g. `Rows matched: 1  Changed: 1  Warnings: 0`.
+    pub info: String,
+}
+
+pub(crate) fn optional<T>(rows: Vec<T>) -> Result<Option<T>> {
+    let mut rows = rows.into_iter();
+    match (rows.next(), rows.next()) {
+        (row, None) => Ok(row),
+        (_, Some(_)) => Err(Error::TooManyRows),
+    }
+}
+
+pub(crate) fn one<T>(rows: Vec<T>) -> Result<T> {
+    optional(rows)?.ok_or(Error::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one() {
+        assert_eq!(one(vec![1]).unwrap(), 1);
+        assert!(matches!(one::<i32>(vec![]), Err(Error::NotFound)));
+        assert!(matches!(one(vec![1, 2]), Err(Error::TooManyRows)));
+    }
+
+    #[test]
+    fn test_optional() {
+        assert_eq!(optional(vec![1]).unwrap(), Some(1));
+        assert_eq!(optional::<i32>(vec![]).unwrap(), None);
+        assert!(matches!(optional(vec![1, 2]), Err(Error::TooManyRows)));
+    }
+}
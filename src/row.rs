@@ -0,0 +1,24 @@
+use mysql_common::value::convert::FromValue;
+
+use crate::{Row, Value};
+
+fn value<'a>(row: &'a Row, column: &str) -> Option<&'a Value> {
+    let index = row
+        .columns_ref()
+        .iter()
+        .position(|c| c.name_ref() == column.as_bytes())?;
+    row.as_ref(index)
+}
+
+/// Reads `column` from `row`, or `None` if it is missing or cannot be converted.
+pub fn get<T: FromValue>(row: &Row, column: &str) -> Option<T> {
+    T::from_value_opt(value(row, column)?.clone()).ok()
+}
+
+/// Reads `column` from `row`, or `Some(None)` if it is missing or `NULL`.
+pub fn get_opt<T: FromValue>(row: &Row, column: &str) -> Option<Option<T>> {
+    match value(row, column) {
+        None | Some(Value::NULL) => Some(None),
+        Some(value) => T::from_value_opt(value.clone()).ok().map(Some),
+    }
+}
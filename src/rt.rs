@@ -0,0 +1,24 @@
+#[cfg(feature = "async")]
+use std::future::Future;
+
+#[cfg(not(any(
+    feature = "tokio",
+    feature = "async-std",
+    feature = "smol",
+    feature = "blocking"
+)))]
+compile_error!("enable one of the `tokio`, `async-std`, `smol` or `blocking` features");
+
+/// Runs a driver future on the caller's runtime.
+///
+/// mysql_async needs a tokio reactor and timer. Under tokio the caller already provides
+/// them; under async-std or smol they come from async-compat's background runtime.
+#[cfg(any(feature = "async-std", feature = "smol"))]
+pub(crate) fn compat<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    async_compat::Compat::new(future)
+}
+
+#[cfg(all(feature = "async", not(any(feature = "async-std", feature = "smol"))))]
+pub(crate) fn compat<F: Future>(future: F) -> F {
+    future
+}
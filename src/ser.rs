@@ -0,0 +1,611 @@
+use serde::ser::{self, Impossible, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{Error, Params, Result, Value};
+
+/// Converts `value` into statement parameters.
+///
+/// Structs and maps become named parameters, so a field `name` binds to `:name`. Sequences
+/// and tuples become positional parameters, and `()` or `None` become no parameters.
+/// `#[serde(rename)]`, `skip_serializing_if` and `flatten` are respected. Field values that
+/// are themselves structs, maps, sequences or data-carrying enum variants are bound as JSON.
+///
+/// ```ignore
+/// db.execute(
+///     "INSERT INTO users (name, email) VALUES (:name, :email)",
+///     Some(mysql::to_params(&user)?),
+/// )
+/// .await?;
+/// ```
+pub fn to_params<T: Serialize + ?Sized>(value: &T) -> Result<Params> {
+    value
+        .serialize(ParamsSerializer)
+        .map_err(|err| Error::Serialize(SerializeError(err.to_string())))
+}
+
+/// A value could not be converted into statement parameters by [`to_params`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerializeError(String);
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SerializeError {}
+
+#[derive(Debug)]
+enum SerError {
+    /// The value is not a scalar and is bound as JSON instead.
+    NotScalar,
+    Custom(String),
+}
+
+impl fmt::Display for SerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerError::NotScalar => write!(f, "value is not a scalar"),
+            SerError::Custom(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SerError {}
+
+impl ser::Error for SerError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerError::Custom(msg.to_string())
+    }
+}
+
+fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, SerError> {
+    match value.serialize(ValueSerializer) {
+        Err(SerError::NotScalar) => serde_json::to_vec(value)
+            .map(Value::Bytes)
+            .map_err(ser::Error::custom),
+        result => result,
+    }
+}
+
+struct ParamsSerializer;
+
+fn unsupported(kind: &str) -> SerError {
+    SerError::Custom(format!(
+        "cannot bind {} as parameters, expected a struct, map or sequence",
+        kind
+    ))
+}
+
+impl ser::Serializer for ParamsSerializer {
+    type Ok = Params;
+    type Error = SerError;
+    type SerializeSeq = PositionalParams;
+    type SerializeTuple = PositionalParams;
+    type SerializeTupleStruct = PositionalParams;
+    type SerializeTupleVariant = Impossible<Params, SerError>;
+    type SerializeMap = NamedParams;
+    type SerializeStruct = NamedParams;
+    type SerializeStructVariant = Impossible<Params, SerError>;
+
+    fn serialize_bool(self, _v: bool) -> Result<Params, SerError> {
+        Err(unsupported("a bool"))
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<Params, SerError> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<Params, SerError> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<Params, SerError> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<Params, SerError> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<Params, SerError> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<Params, SerError> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<Params, SerError> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Params, SerError> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Params, SerError> {
+        Err(unsupported("a float"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Params, SerError> {
+        Err(unsupported("a float"))
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Params, SerError> {
+        Err(unsupported("a char"))
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<Params, SerError> {
+        Err(unsupported("a string"))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Params, SerError> {
+        Err(unsupported("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<Params, SerError> {
+        Ok(Params::Empty)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Params, SerError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Params, SerError> {
+        Ok(Params::Empty)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Params, SerError> {
+        Ok(Params::Empty)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Params, SerError> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Params, SerError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Params, SerError> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<PositionalParams, SerError> {
+        Ok(PositionalParams(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<PositionalParams, SerError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<PositionalParams, SerError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerError> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<NamedParams, SerError> {
+        Ok(NamedParams {
+            params: HashMap::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<NamedParams, SerError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerError> {
+        Err(unsupported("an enum"))
+    }
+}
+
+struct PositionalParams(Vec<Value>);
+
+impl PositionalParams {
+    fn finish(self) -> Params {
+        if self.0.is_empty() {
+            Params::Empty
+        } else {
+            Params::Positional(self.0)
+        }
+    }
+}
+
+impl ser::SerializeSeq for PositionalParams {
+    type Ok = Params;
+    type Error = SerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.0.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Params, SerError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for PositionalParams {
+    type Ok = Params;
+    type Error = SerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Params, SerError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for PositionalParams {
+    type Ok = Params;
+    type Error = SerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Params, SerError> {
+        Ok(self.finish())
+    }
+}
+
+struct NamedParams {
+    params: HashMap<Vec<u8>, Value>,
+    key: Option<Vec<u8>>,
+}
+
+impl NamedParams {
+    fn finish(self) -> Params {
+        if self.params.is_empty() {
+            Params::Empty
+        } else {
+            Params::Named(self.params)
+        }
+    }
+}
+
+impl ser::SerializeMap for NamedParams {
+    type Ok = Params;
+    type Error = SerError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerError> {
+        match to_value(key)? {
+            Value::Bytes(key) => self.key = Some(key),
+            _ => return Err(ser::Error::custom("parameter names must be strings")),
+        }
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("value serialized before key"))?;
+        self.params.insert(key, to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Params, SerError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for NamedParams {
+    type Ok = Params;
+    type Error = SerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerError> {
+        self.params
+            .insert(key.as_bytes().to_vec(), to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Params, SerError> {
+        Ok(self.finish())
+    }
+}
+
+/// Serializes scalars into a [`Value`], failing with [`SerError::NotScalar`] otherwise.
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = SerError;
+    type SerializeSeq = Impossible<Value, SerError>;
+    type SerializeTuple = Impossible<Value, SerError>;
+    type SerializeTupleStruct = Impossible<Value, SerError>;
+    type SerializeTupleVariant = Impossible<Value, SerError>;
+    type SerializeMap = Impossible<Value, SerError>;
+    type SerializeStruct = Impossible<Value, SerError>;
+    type SerializeStructVariant = Impossible<Value, SerError>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, SerError> {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, SerError> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, SerError> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, SerError> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, SerError> {
+        Ok(Value::Int(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, SerError> {
+        Ok(Value::UInt(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, SerError> {
+        Ok(Value::UInt(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, SerError> {
+        Ok(Value::UInt(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, SerError> {
+        Ok(Value::UInt(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, SerError> {
+        Ok(Value::Float(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, SerError> {
+        Ok(Value::Double(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, SerError> {
+        Ok(Value::Bytes(v.to_string().into_bytes()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, SerError> {
+        Ok(Value::Bytes(v.as_bytes().to_vec()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, SerError> {
+        Ok(Value::Bytes(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Value, SerError> {
+        Ok(Value::NULL)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, SerError> {
+        to_value(value)
+    }
+
+    fn serialize_unit(self) -> Result<Value, SerError> {
+        Ok(Value::NULL)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, SerError> {
+        Ok(Value::NULL)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, SerError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, SerError> {
+        to_value(value)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Value, SerError> {
+        Err(SerError::NotScalar)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerError> {
+        Err(SerError::NotScalar)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerError> {
+        Err(SerError::NotScalar)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerError> {
+        Err(SerError::NotScalar)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerError> {
+        Err(SerError::NotScalar)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerError> {
+        Err(SerError::NotScalar)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerError> {
+        Err(SerError::NotScalar)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerError> {
+        Err(SerError::NotScalar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Address {
+        city: String,
+    }
+
+    #[derive(Serialize)]
+    struct Audit {
+        created_by: &'static str,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Role {
+        Admin,
+    }
+
+    #[derive(Serialize)]
+    struct User {
+        id: u32,
+        #[serde(rename = "user_name")]
+        name: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        email: Option<&'static str>,
+        nickname: Option<&'static str>,
+        active: bool,
+        role: Role,
+        address: Address,
+        tags: Vec<&'static str>,
+        #[serde(flatten)]
+        audit: Audit,
+    }
+
+    fn named(params: Params) -> HashMap<Vec<u8>, Value> {
+        match params {
+            Params::Named(params) => params,
+            params => panic!("expected named params, got {:?}", params),
+        }
+    }
+
+    #[test]
+    fn test_struct() {
+        let params = named(
+            to_params(&User {
+                id: 1,
+                name: "alice",
+                email: None,
+                nickname: None,
+                active: true,
+                role: Role::Admin,
+                address: Address {
+                    city: "Aarhus".to_string(),
+                },
+                tags: vec!["a", "b"],
+                audit: Audit { created_by: "bob" },
+            })
+            .unwrap(),
+        );
+        let get = |name: &str| params.get(name.as_bytes()).cloned();
+        assert_eq!(get("id"), Some(Value::UInt(1)));
+        assert_eq!(get("user_name"), Some(Value::from("alice")));
+        assert_eq!(get("email"), None);
+        assert_eq!(get("nickname"), Some(Value::NULL));
+        assert_eq!(get("active"), Some(Value::Int(1)));
+        assert_eq!(get("role"), Some(Value::from("admin")));
+        assert_eq!(get("address"), Some(Value::from(r#"{"city":"Aarhus"}"#)));
+        assert_eq!(get("tags"), Some(Value::from(r#"["a","b"]"#)));
+        assert_eq!(get("created_by"), Some(Value::from("bob")));
+        assert_eq!(params.len(), 8);
+    }
+
+    #[test]
+    fn test_map_and_tuple() {
+        let params = named(to_params(&HashMap::from([("id", 5)])).unwrap());
+        assert_eq!(params.get(&b"id"[..]), Some(&Value::Int(5)));
+
+        let params = to_params(&(1, "a")).unwrap();
+        assert_eq!(
+            params,
+            Params::Positional(vec![Value::Int(1), Value::from("a")])
+        );
+        assert_eq!(to_params(&()).unwrap(), Params::Empty);
+    }
+
+    #[test]
+    fn test_scalar_is_rejected() {
+        let err = to_params(&5).unwrap_err();
+        assert!(matches!(err, Error::Serialize(_)));
+        assert_eq!(
+            err.to_string(),
+            "failed to serialize parameters: cannot bind an integer as parameters, \
+             expected a struct, map or sequence"
+        );
+    }
+}
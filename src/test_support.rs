@@ -0,0 +1,1208 @@
+//! An in-process stand-in for a MySQL server, so tests can run queries end to end without one.
+//!
+//! ```ignore
+//! use mysql::test_support::{FakeServer, Response, Script};
+//!
+//! let server = FakeServer::start(Script::new().with_response(
+//!     "SELECT id, name FROM users WHERE id = ?",
+//!     Response::rows(&["id", "name"], vec![vec![1.into(), "alice".into()]]),
+//! ));
+//! let users: Vec<(i32, String)> = server
+//!     .database()
+//!     .select("SELECT id, name FROM users WHERE id = :id", Some(params! { "id" => 1 }))
+//!     .await?;
+//! ```
+//!
+//! The server speaks just enough of the protocol for the driver: the handshake (any
+//! credentials are accepted), `COM_QUERY`, `COM_STMT_PREPARE`/`EXECUTE`/`CLOSE`, `COM_PING`,
+//! `COM_RESET_CONNECTION` and `COM_INIT_DB`, answering with text or binary result sets.
+//!
+//! The driver rewrites named parameters to `?` before sending a statement, so scripted
+//! queries are written with `?`. The driver's own `SELECT @@...` settings queries and
+//! `SET`, `DO` and transaction statements are answered with defaults unless scripted.
+//!
+//! Code written against [`Executor`] can skip the network entirely with a [`MockExecutor`],
+//! which answers from the same [`Script`]s.
+//!
+//! Tests that need a real server can use `#[mysql::test]`, which runs each test on its own
+//! scratch database, see [`TestSetup`].
+
+use bytes::BufMut;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use mysql_common::constants::{CapabilityFlags, ColumnFlags, ColumnType, StatusFlags};
+use mysql_common::io::{BufMutExt, ParseBuf};
+use mysql_common::packets::Column;
+use mysql_common::proto::MySerialize;
+use mysql_common::row::new_row;
+use mysql_common::value::{BinValue, ValueDeserializer};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{BuildHasher, RandomState};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+use crate::fixtures::{Fixture, Fixtures};
+use crate::migrate::Migrator;
+use crate::{
+    config, de, in_list, Database, Error, ExecResult, Executor, FromRow, Params, Result, Row,
+    TxOptions, Value,
+};
+
+const COM_QUIT: u8 = 0x01;
+const COM_INIT_DB: u8 = 0x02;
+const COM_QUERY: u8 = 0x03;
+const COM_PING: u8 = 0x0e;
+const COM_CHANGE_USER: u8 = 0x11;
+const COM_STMT_PREPARE: u8 = 0x16;
+const COM_STMT_EXECUTE: u8 = 0x17;
+const COM_STMT_SEND_LONG_DATA: u8 = 0x18;
+const COM_STMT_CLOSE: u8 = 0x19;
+const COM_STMT_RESET: u8 = 0x1a;
+const COM_RESET_CONNECTION: u8 = 0x1f;
+
+const ER_UNKNOWN_COM_ERROR: u16 = 1047;
+const ER_UNKNOWN_ERROR: u16 = 1105;
+
+const MAX_ALLOWED_PACKET: i64 = 4 * 1024 * 1024;
+const SCRAMBLE: &[u8; 20] = b"0123456789abcdefghij";
+const UTF8MB4: u16 = 255;
+const BINARY: u16 = 63;
+
+/// What the server answers to a statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    Ok(ExecResult),
+    /// A result set. Each column takes its type from its first non-`NULL` value.
+    Rows {
+        columns: Vec<String>,
+        rows: Vec<Vec<Value>>,
+    },
+    Error {
+        code: u16,
+        message: String,
+    },
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Response::Ok(ExecResult::default())
+    }
+
+    pub fn affected_rows(affected_rows: u64) -> Self {
+        Response::Ok(ExecResult {
+            affected_rows,
+            ..ExecResult::default()
+        })
+    }
+
+    pub fn rows(columns: &[&str], rows: Vec<Vec<Value>>) -> Self {
+        Response::Rows {
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            rows,
+        }
+    }
+
+    pub fn error(code: u16, message: impl Into<String>) -> Self {
+        Response::Error {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+type Handler = Box<dyn Fn(&str, &[Value]) -> Option<Response> + Send + Sync>;
+
+/// The responses a [`FakeServer`] gives, checked in the order they were added.
+#[derive(Default)]
+pub struct Script {
+    responses: Vec<(String, Response)>,
+    handlers: Vec<Handler>,
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Script::default()
+    }
+
+    /// Answers `query`, compared after trimming whitespace, with `response` every time.
+    pub fn with_response(mut self, query: &str, response: Response) -> Self {
+        self.responses.push((query.trim().to_string(), response));
+        self
+    }
+
+    /// Answers statements for which `handler` returns a response, given the query and its
+    /// parameters. Fixed responses are checked first.
+    pub fn with_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&str, &[Value]) -> Option<Response> + Send + Sync + 'static,
+    {
+        self.handlers.push(Box::new(handler));
+        self
+    }
+
+    fn respond(&self, query: &str, params: &[Value]) -> Option<Response> {
+        let trimmed = query.trim();
+        self.responses
+            .iter()
+            .find(|(scripted, _)| scripted == trimmed)
+            .map(|(_, response)| response.clone())
+            .or_else(|| {
+                self.handlers
+                    .iter()
+                    .find_map(|handler| handler(query, params))
+            })
+    }
+}
+
+/// A statement received by a [`FakeServer`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Received {
+    pub query: String,
+    /// The bound values of a prepared statement; empty for plain queries.
+    pub params: Vec<Value>,
+}
+
+struct Shared {
+    script: Script,
+    received: Mutex<Vec<Received>>,
+    streams: Mutex<Vec<TcpStream>>,
+    stopped: AtomicBool,
+}
+
+/// A fake server listening on a local port until it is dropped.
+pub struct FakeServer {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+}
+
+impl FakeServer {
+    /// Starts listening on an ephemeral port, serving each connection on its own thread.
+    pub fn start(script: Script) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind the fake server");
+        let addr = listener.local_addr().expect("fake server address");
+        let shared = Arc::new(Shared {
+            script,
+            received: Mutex::new(Vec::new()),
+            streams: Mutex::new(Vec::new()),
+            stopped: AtomicBool::new(false),
+        });
+        let accepting = shared.clone();
+        thread::spawn(move || {
+            for (id, stream) in listener.incoming().enumerate() {
+                if accepting.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                if let Ok(clone) = stream.try_clone() {
+                    accepting.streams.lock().unwrap().push(clone);
+                }
+                let shared = accepting.clone();
+                thread::spawn(move || {
+                    // Errors only mean the client went away.
+                    let _ = Session::new(stream, shared).run(id as u32 + 1);
+                });
+            }
+        });
+        FakeServer { addr, shared }
+    }
+
+    pub fn url(&self) -> String {
+        format!("mysql://root@{}/test?prefer_socket=false", self.addr)
+    }
+
+    /// A database handle connected to this server.
+    pub fn database(&self) -> Database {
+        Database::from_url(&self.url()).expect("fake server URL is valid")
+    }
+
+    /// Every statement received so far, in order, apart from the driver's settings queries.
+    pub fn received(&self) -> Vec<Received> {
+        self.shared.received.lock().unwrap().clone()
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        // Wakes the accept loop so it sees the flag.
+        let _ = TcpStream::connect(self.addr);
+        for stream in self.shared.streams.lock().unwrap().drain(..) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// An [`Executor`] that answers from a [`Script`] without a server, recording every statement.
+///
+/// Statements are recorded and matched as a [`FakeServer`] would see them, with named
+/// parameters and [`InList`](crate::InList)s rewritten to `?`, so the same scripts work for
+/// both. [`Executor::transaction`] records `START TRANSACTION` and then `COMMIT` or
+/// `ROLLBACK`, and runs its closure once with the same mock.
+pub struct MockExecutor {
+    script: Script,
+    received: Mutex<Vec<Received>>,
+}
+
+impl MockExecutor {
+    pub fn new(script: Script) -> Self {
+        MockExecutor {
+            script,
+            received: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Every statement run so far, in order.
+    pub fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+
+    fn run(&self, query: &str, params: Option<Params>) -> Result<Response> {
+        let (query, params) = in_list::positional(query, &params.unwrap_or(Params::Empty), None)?;
+        let params = params.into_iter().map(over_the_wire).collect::<Vec<_>>();
+        let response = self
+            .script
+            .respond(&query, &params)
+            .or_else(|| builtin(&query.trim_start().to_ascii_uppercase()));
+        self.received.lock().unwrap().push(Received {
+            query: query.clone(),
+            params,
+        });
+        match response {
+            Some(Response::Error { code, message }) => Err(Error::Server {
+                code,
+                state: "HY000".to_string(),
+                message,
+            }),
+            Some(response) => Ok(response),
+            None => Err(Error::Server {
+                code: ER_UNKNOWN_ERROR,
+                state: "HY000".to_string(),
+                message: format!("the mock executor has no response for `{}`", query),
+            }),
+        }
+    }
+
+    fn rows(&self, query: &str, params: Option<Params>) -> Result<Vec<Row>> {
+        let Response::Rows { columns, rows } = self.run(query, params)? else {
+            return Ok(Vec::new());
+        };
+        let columns: Arc<[Column]> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let value = rows
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .find(|value| **value != Value::NULL);
+                let (column_type, flags) = column_type(value);
+                Column::new(column_type)
+                    .with_name(name.as_bytes())
+                    .with_flags(flags)
+            })
+            .collect();
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                new_row(
+                    row.into_iter().map(over_the_wire).collect(),
+                    columns.clone(),
+                )
+            })
+            .collect())
+    }
+}
+
+impl Executor for MockExecutor {
+    type Transaction = MockExecutor;
+
+    fn select<T: FromRow + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<Vec<T>>> + Send {
+        let rows = self.rows(query, params_map);
+        async move {
+            Ok(rows?
+                .into_iter()
+                .map(T::from_row_opt)
+                .collect::<Result<_, _>>()?)
+        }
+    }
+
+    fn select_as<T: DeserializeOwned + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<Vec<T>>> + Send {
+        let rows = self.rows(query, params_map);
+        async move {
+            Ok(rows?
+                .into_iter()
+                .map(de::from_row)
+                .collect::<Result<_, _>>()?)
+        }
+    }
+
+    fn execute(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> impl Future<Output = Result<ExecResult>> + Send {
+        let result = self.run(query, params_map).map(|response| match response {
+            Response::Ok(result) => result,
+            _ => ExecResult::default(),
+        });
+        async move { result }
+    }
+
+    async fn transaction<T, F>(&self, _options: TxOptions, mut f: F) -> Result<T>
+    where
+        T: Send,
+        F: for<'t> FnMut(&'t MockExecutor) -> BoxFuture<'t, Result<T>> + Send,
+    {
+        self.run("START TRANSACTION", None)?;
+        match f(self).await {
+            Ok(value) => {
+                self.run("COMMIT", None)?;
+                Ok(value)
+            }
+            Err(err) => {
+                let _ = self.run("ROLLBACK", None);
+                Err(err)
+            }
+        }
+    }
+}
+
+/// How `#[mysql::test]` prepares the scratch database each test runs on.
+///
+/// ```ignore
+/// #[mysql::test(migrations = "migrations", fixtures("tests/fixtures/users.yaml"))]
+/// async fn test_rename(db: Database) {
+///     assert!(rename(&db, 1, "bob").await.unwrap());
+/// }
+/// ```
+#[derive(Default)]
+pub struct TestSetup {
+    url: Option<String>,
+    migrator: Option<Migrator>,
+    fixtures: Vec<Fixture>,
+}
+
+impl TestSetup {
+    pub fn new() -> Self {
+        TestSetup::default()
+    }
+
+    /// Creates the scratch database on the server at `url` instead of the one in
+    /// `DATABASE_URL`.
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    pub fn with_migrator(mut self, migrator: Migrator) -> Self {
+        self.migrator = Some(migrator);
+        self
+    }
+
+    /// Loads `fixture` after the migrations, see [`Fixtures::load`].
+    pub fn with_fixture(mut self, fixture: Fixture) -> Self {
+        self.fixtures.push(fixture);
+        self
+    }
+
+    /// Creates a database named after `name` with a random suffix, sets it up and runs `test`
+    /// with a handle to it on a new runtime. The database is dropped afterwards, even if
+    /// `test` panics.
+    pub fn run<F, Fut>(self, name: &str, test: F) -> Fut::Output
+    where
+        F: FnOnce(Database) -> Fut,
+        Fut: Future,
+    {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to start a runtime for the test");
+        let url = match &self.url {
+            Some(url) => url.clone(),
+            None => config::database_url().unwrap_or_else(|err| panic!("{}", err)),
+        };
+        let opts = config::opts_from_url(&url).unwrap_or_else(|err| panic!("{}", err));
+        let name = scratch_name(name);
+        let admin = Database::new(opts.clone());
+        if let Err(err) =
+            runtime.block_on(admin.execute(&format!("CREATE DATABASE `{}`", name), None))
+        {
+            panic!("failed to create database `{}`: {}", name, err);
+        }
+
+        let database = Database::new(opts.db_name(Some(&name)));
+        let outcome = runtime.block_on(
+            AssertUnwindSafe(async {
+                if let Err(err) = self.prepare(&database).await {
+                    panic!("failed to set up database `{}`: {}", name, err);
+                }
+                test(database.clone()).await
+            })
+            .catch_unwind(),
+        );
+        let _ = runtime.block_on(database.disconnect());
+        let dropped = runtime.block_on(admin.execute(&format!("DROP DATABASE `{}`", name), None));
+        let _ = runtime.block_on(admin.disconnect());
+        match (outcome, dropped) {
+            (Err(payload), _) => panic::resume_unwind(payload),
+            (Ok(_), Err(err)) => panic!("failed to drop database `{}`: {}", name, err),
+            (Ok(output), Ok(_)) => output,
+        }
+    }
+
+    async fn prepare(&self, database: &Database) -> Result<()> {
+        if let Some(migrator) = &self.migrator {
+            migrator.run(database).await?;
+        }
+        Fixtures::new(self.fixtures.clone()).load(database).await
+    }
+}
+
+/// `test_<name>_<random>`, keeping within the 64 characters MySQL allows.
+fn scratch_name(name: &str) -> String {
+    let name = name
+        .rsplit("::")
+        .next()
+        .unwrap_or(name)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .take(40)
+        .collect::<String>();
+    let random = RandomState::new().hash_one((std::process::id(), SystemTime::now()));
+    format!("test_{}_{:016x}", name, random)
+}
+
+#[derive(Default)]
+struct Statement {
+    query: String,
+    params: usize,
+    types: Vec<(ColumnType, ColumnFlags)>,
+    long_data: HashMap<usize, Vec<u8>>,
+}
+
+struct Session {
+    stream: TcpStream,
+    shared: Arc<Shared>,
+    seq: u8,
+    deprecate_eof: bool,
+    in_transaction: bool,
+    statements: HashMap<u32, Statement>,
+    next_statement: u32,
+}
+
+impl Session {
+    fn new(stream: TcpStream, shared: Arc<Shared>) -> Self {
+        Session {
+            stream,
+            shared,
+            seq: 0,
+            deprecate_eof: false,
+            in_transaction: false,
+            statements: HashMap::new(),
+            next_statement: 1,
+        }
+    }
+
+    fn run(mut self, connection_id: u32) -> io::Result<()> {
+        self.handshake(connection_id)?;
+        loop {
+            let packet = self.read()?;
+            let Some((&command, body)) = packet.split_first() else {
+                continue;
+            };
+            match command {
+                COM_QUIT => return Ok(()),
+                COM_INIT_DB | COM_PING | COM_RESET_CONNECTION | COM_CHANGE_USER => {
+                    self.write_ok(&ExecResult::default())?
+                }
+                COM_QUERY => {
+                    let query = String::from_utf8_lossy(body).into_owned();
+                    let response = self.respond(query, Vec::new());
+                    self.write_response(&response, false)?;
+                }
+                COM_STMT_PREPARE => self.prepare(String::from_utf8_lossy(body).into_owned())?,
+                COM_STMT_EXECUTE => self.execute(body)?,
+                COM_STMT_SEND_LONG_DATA => {
+                    if let (Some(id), Some(param)) = (read_u32(body, 0), read_u16(body, 4)) {
+                        if let Some(statement) = self.statements.get_mut(&id) {
+                            statement
+                                .long_data
+                                .entry(param as usize)
+                                .or_default()
+                                .extend_from_slice(&body[6..]);
+                        }
+                    }
+                }
+                COM_STMT_CLOSE => {
+                    if let Some(id) = read_u32(body, 0) {
+                        self.statements.remove(&id);
+                    }
+                }
+                COM_STMT_RESET => {
+                    if let Some(statement) =
+                        read_u32(body, 0).and_then(|id| self.statements.get_mut(&id))
+                    {
+                        statement.long_data.clear();
+                    }
+                    self.write_ok(&ExecResult::default())?
+                }
+                _ => self.write_err(ER_UNKNOWN_COM_ERROR, "unknown command")?,
+            }
+        }
+    }
+
+    fn handshake(&mut self, connection_id: u32) -> io::Result<()> {
+        let capabilities = CapabilityFlags::CLIENT_LONG_PASSWORD
+            | CapabilityFlags::CLIENT_LONG_FLAG
+            | CapabilityFlags::CLIENT_CONNECT_WITH_DB
+            | CapabilityFlags::CLIENT_PROTOCOL_41
+            | CapabilityFlags::CLIENT_TRANSACTIONS
+            | CapabilityFlags::CLIENT_SECURE_CONNECTION
+            | CapabilityFlags::CLIENT_MULTI_STATEMENTS
+            | CapabilityFlags::CLIENT_MULTI_RESULTS
+            | CapabilityFlags::CLIENT_PS_MULTI_RESULTS
+            | CapabilityFlags::CLIENT_PLUGIN_AUTH
+            | CapabilityFlags::CLIENT_CONNECT_ATTRS
+            | CapabilityFlags::CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA
+            | CapabilityFlags::CLIENT_DEPRECATE_EOF;
+        let bits = capabilities.bits();
+        let mut packet = vec![10];
+        packet.extend_from_slice(b"8.0.36-fake\0");
+        packet.put_u32_le(connection_id);
+        packet.extend_from_slice(&SCRAMBLE[..8]);
+        packet.push(0);
+        packet.put_u16_le(bits as u16);
+        packet.push(UTF8MB4 as u8);
+        packet.put_u16_le(self.status().bits());
+        packet.put_u16_le((bits >> 16) as u16);
+        packet.push(SCRAMBLE.len() as u8 + 1);
+        packet.extend_from_slice(&[0; 10]);
+        packet.extend_from_slice(&SCRAMBLE[8..]);
+        packet.push(0);
+        packet.extend_from_slice(b"mysql_native_password\0");
+        self.write(&packet)?;
+
+        // Any credentials are accepted; only the negotiated capabilities matter.
+        let response = self.read()?;
+        let client = CapabilityFlags::from_bits_truncate(read_u32(&response, 0).unwrap_or(0));
+        self.deprecate_eof = client.contains(CapabilityFlags::CLIENT_DEPRECATE_EOF);
+        self.write_ok(&ExecResult::default())
+    }
+
+    fn prepare(&mut self, query: String) -> io::Result<()> {
+        let params = in_list::placeholders(&query)
+            .iter()
+            .filter(|placeholder| placeholder.name.is_none())
+            .count();
+        let id = self.next_statement;
+        self.next_statement += 1;
+        self.statements.insert(
+            id,
+            Statement {
+                query,
+                params,
+                ..Statement::default()
+            },
+        );
+
+        // Result set columns are sent with each execution, so none are declared here.
+        let mut packet = vec![0];
+        packet.put_u32_le(id);
+        packet.put_u16_le(0);
+        packet.put_u16_le(params as u16);
+        packet.push(0);
+        packet.put_u16_le(0);
+        self.write(&packet)?;
+        if params > 0 {
+            for _ in 0..params {
+                self.write(&column_definition(
+                    "?",
+                    ColumnType::MYSQL_TYPE_VAR_STRING,
+                    ColumnFlags::empty(),
+                ))?;
+            }
+            if !self.deprecate_eof {
+                self.write_eof()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, body: &[u8]) -> io::Result<()> {
+        let Some(id) = read_u32(body, 0) else {
+            return self.write_err(ER_UNKNOWN_ERROR, "malformed COM_STMT_EXECUTE");
+        };
+        let Some(mut statement) = self.statements.remove(&id) else {
+            return self.write_err(ER_UNKNOWN_ERROR, "unknown prepared statement");
+        };
+        let params = read_params(&mut statement, &body[9..]);
+        let query = statement.query.clone();
+        statement.long_data.clear();
+        self.statements.insert(id, statement);
+        match params {
+            Some(params) => {
+                let response = self.respond(query, params);
+                self.write_response(&response, true)
+            }
+            None => self.write_err(ER_UNKNOWN_ERROR, "malformed COM_STMT_EXECUTE"),
+        }
+    }
+
+    fn respond(&mut self, query: String, params: Vec<Value>) -> Response {
+        if let Some(response) = settings(&query) {
+            return response;
+        }
+        let response = self
+            .shared
+            .script
+            .respond(&query, &params)
+            .or_else(|| self.builtin(&query));
+        self.shared.received.lock().unwrap().push(Received {
+            query: query.clone(),
+            params,
+        });
+        response.unwrap_or_else(|| {
+            Response::error(
+                ER_UNKNOWN_ERROR,
+                format!("the fake server has no response for `{}`", query),
+            )
+        })
+    }
+
+    /// Answers the statements the driver and the crate send on their own.
+    fn builtin(&mut self, query: &str) -> Option<Response> {
+        let statement = query.trim_start().to_ascii_uppercase();
+        if statement.starts_with("START TRANSACTION") || statement.starts_with("BEGIN") {
+            self.in_transaction = true;
+        } else if statement.starts_with("COMMIT") || statement.starts_with("ROLLBACK") {
+            self.in_transaction = false;
+        }
+        builtin(&statement)
+    }
+
+    fn status(&self) -> StatusFlags {
+        let mut status = StatusFlags::SERVER_STATUS_AUTOCOMMIT;
+        if self.in_transaction {
+            status |= StatusFlags::SERVER_STATUS_IN_TRANS;
+        }
+        status
+    }
+
+    fn write_response(&mut self, response: &Response, binary: bool) -> io::Result<()> {
+        match response {
+            Response::Ok(result) => self.write_ok(result),
+            Response::Error { code, message } => self.write_err(*code, message),
+            Response::Rows { columns, rows } => {
+                let mut packet = Vec::new();
+                packet.put_lenenc_int(columns.len() as u64);
+                self.write(&packet)?;
+                for (i, name) in columns.iter().enumerate() {
+                    let value = rows
+                        .iter()
+                        .filter_map(|row| row.get(i))
+                        .find(|value| **value != Value::NULL);
+                    let (column_type, flags) = column_type(value);
+                    self.write(&column_definition(name, column_type, flags))?;
+                }
+                if !self.deprecate_eof {
+                    self.write_eof()?;
+                }
+                for row in rows {
+                    let packet = if binary {
+                        binary_row(row)
+                    } else {
+                        text_row(row)
+                    };
+                    self.write(&packet)?;
+                }
+                if self.deprecate_eof {
+                    let mut packet = vec![0xfe];
+                    self.put_ok_body(&mut packet, &ExecResult::default());
+                    self.write(&packet)
+                } else {
+                    self.write_eof()
+                }
+            }
+        }
+    }
+
+    fn put_ok_body(&self, packet: &mut Vec<u8>, result: &ExecResult) {
+        packet.put_lenenc_int(result.affected_rows);
+        packet.put_lenenc_int(result.last_insert_id.unwrap_or(0));
+        packet.put_u16_le(self.status().bits());
+        packet.put_u16_le(result.warnings);
+        packet.extend_from_slice(result.info.as_bytes());
+    }
+
+    fn write_ok(&mut self, result: &ExecResult) -> io::Result<()> {
+        let mut packet = vec![0];
+        self.put_ok_body(&mut packet, result);
+        self.write(&packet)
+    }
+
+    fn write_eof(&mut self) -> io::Result<()> {
+        let mut packet = vec![0xfe];
+        packet.put_u16_le(0);
+        packet.put_u16_le(self.status().bits());
+        self.write(&packet)
+    }
+
+    fn write_err(&mut self, code: u16, message: &str) -> io::Result<()> {
+        let mut packet = vec![0xff];
+        packet.put_u16_le(code);
+        packet.extend_from_slice(b"#HY000");
+        packet.extend_from_slice(message.as_bytes());
+        self.write(&packet)
+    }
+
+    fn read(&mut self) -> io::Result<Vec<u8>> {
+        let mut payload = Vec::new();
+        loop {
+            let mut header = [0; 4];
+            self.stream.read_exact(&mut header)?;
+            let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+            self.seq = header[3].wrapping_add(1);
+            let start = payload.len();
+            payload.resize(start + len, 0);
+            self.stream.read_exact(&mut payload[start..])?;
+            // A full-length packet is continued by the next one.
+            if len < 0xff_ff_ff {
+                return Ok(payload);
+            }
+        }
+    }
+
+    fn write(&mut self, payload: &[u8]) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(payload.len() + 4);
+        packet.extend_from_slice(&(payload.len() as u32).to_le_bytes()[..3]);
+        packet.push(self.seq);
+        packet.extend_from_slice(payload);
+        self.seq = self.seq.wrapping_add(1);
+        self.stream.write_all(&packet)
+    }
+}
+
+/// The default answer to the statements the driver and the crate send on their own, given
+/// the statement in upper case.
+fn builtin(statement: &str) -> Option<Response> {
+    [
+        "START TRANSACTION",
+        "BEGIN",
+        "COMMIT",
+        "ROLLBACK",
+        "SET ",
+        "DO ",
+    ]
+    .iter()
+    .any(|prefix| statement.starts_with(prefix))
+    .then(Response::ok)
+}
+
+/// `value` as the driver decodes it after a round trip, which reads unsigned integers that
+/// fit in an `i64` back as signed.
+fn over_the_wire(value: Value) -> Value {
+    match value {
+        Value::UInt(x) => i64::try_from(x).map_or(Value::UInt(x), Value::Int),
+        value => value,
+    }
+}
+
+fn settings(query: &str) -> Option<Response> {
+    let variables = query.trim().strip_prefix("SELECT ")?;
+    let mut columns = Vec::new();
+    let mut values = Vec::new();
+    for variable in variables.split(',').map(str::trim) {
+        let name = variable.strip_prefix("@@")?;
+        let name = name
+            .strip_prefix("session.")
+            .or_else(|| name.strip_prefix("global."))
+            .unwrap_or(name);
+        columns.push(variable.to_string());
+        values.push(match name.to_ascii_lowercase().as_str() {
+            "max_allowed_packet" => Value::Int(MAX_ALLOWED_PACKET),
+            "wait_timeout" => Value::Int(28800),
+            // The driver reads this as a string; an empty path fails fast if it is tried.
+            "socket" => Value::from(""),
+            _ => Value::NULL,
+        });
+    }
+    Some(Response::Rows {
+        columns,
+        rows: vec![values],
+    })
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Reads the values of a `COM_STMT_EXECUTE` after its statement id, flags and iteration count.
+fn read_params(statement: &mut Statement, body: &[u8]) -> Option<Vec<Value>> {
+    let count = statement.params;
+    if count == 0 {
+        return Some(Vec::new());
+    }
+    let (null_bitmap, rest) = body.split_at_checked(count.div_ceil(8))?;
+    let (&bound, mut rest) = rest.split_first()?;
+    if bound == 1 {
+        let (types, values) = rest.split_at_checked(count * 2)?;
+        statement.types = types
+            .chunks(2)
+            .map(|pair| {
+                let column_type = ColumnType::try_from(pair[0]).ok()?;
+                let flags = if pair[1] & 0x80 != 0 {
+                    ColumnFlags::UNSIGNED_FLAG
+                } else {
+                    ColumnFlags::empty()
+                };
+                Some((column_type, flags))
+            })
+            .collect::<Option<_>>()?;
+        rest = values;
+    }
+    let mut buf = ParseBuf(rest);
+    (0..count)
+        .map(|i| {
+            if null_bitmap[i / 8] & (1 << (i % 8)) != 0 {
+                return Some(Value::NULL);
+            }
+            if let Some(data) = statement.long_data.get(&i) {
+                return Some(Value::Bytes(data.clone()));
+            }
+            let ctx = *statement.types.get(i)?;
+            buf.parse::<ValueDeserializer<BinValue>>(ctx)
+                .ok()
+                .map(|value| value.0)
+        })
+        .collect()
+}
+
+fn column_type(value: Option<&Value>) -> (ColumnType, ColumnFlags) {
+    match value {
+        Some(Value::Int(_)) => (ColumnType::MYSQL_TYPE_LONGLONG, ColumnFlags::empty()),
+        Some(Value::UInt(_)) => (ColumnType::MYSQL_TYPE_LONGLONG, ColumnFlags::UNSIGNED_FLAG),
+        Some(Value::Float(_)) => (ColumnType::MYSQL_TYPE_FLOAT, ColumnFlags::empty()),
+        Some(Value::Double(_)) => (ColumnType::MYSQL_TYPE_DOUBLE, ColumnFlags::empty()),
+        Some(Value::Date(..)) => (ColumnType::MYSQL_TYPE_DATETIME, ColumnFlags::empty()),
+        Some(Value::Time(..)) => (ColumnType::MYSQL_TYPE_TIME, ColumnFlags::empty()),
+        Some(Value::Bytes(_) | Value::NULL) | None => {
+            (ColumnType::MYSQL_TYPE_VAR_STRING, ColumnFlags::empty())
+        }
+    }
+}
+
+fn column_definition(name: &str, column_type: ColumnType, flags: ColumnFlags) -> Vec<u8> {
+    let mut packet = Vec::new();
+    for field in ["def", "", "", "", name, name] {
+        packet.put_lenenc_str(field.as_bytes());
+    }
+    packet.put_lenenc_int(0x0c);
+    let charset = match column_type {
+        ColumnType::MYSQL_TYPE_VAR_STRING => UTF8MB4,
+        _ => BINARY,
+    };
+    packet.put_u16_le(charset);
+    packet.put_u32_le(255);
+    packet.push(column_type as u8);
+    packet.put_u16_le(flags.bits());
+    packet.push(0);
+    packet.put_u16_le(0);
+    packet
+}
+
+fn text_row(row: &[Value]) -> Vec<u8> {
+    let mut packet = Vec::new();
+    for value in row {
+        let text = match value {
+            Value::NULL => {
+                packet.push(0xfb);
+                continue;
+            }
+            Value::Bytes(bytes) => bytes.clone(),
+            Value::Int(value) => value.to_string().into_bytes(),
+            Value::UInt(value) => value.to_string().into_bytes(),
+            Value::Float(value) => value.to_string().into_bytes(),
+            Value::Double(value) => value.to_string().into_bytes(),
+            Value::Date(year, month, day, hour, minute, second, micros) => {
+                let mut text = format!(
+                    "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                    year, month, day, hour, minute, second
+                );
+                if *micros > 0 {
+                    text += &format!(".{:06}", micros);
+                }
+                text.into_bytes()
+            }
+            Value::Time(negative, days, hours, minutes, seconds, micros) => {
+                let mut text = format!(
+                    "{}{:02}:{:02}:{:02}",
+                    if *negative { "-" } else { "" },
+                    days * 24 + u32::from(*hours),
+                    minutes,
+                    seconds
+                );
+                if *micros > 0 {
+                    text += &format!(".{:06}", micros);
+                }
+                text.into_bytes()
+            }
+        };
+        packet.put_lenenc_str(&text);
+    }
+    packet
+}
+
+fn binary_row(row: &[Value]) -> Vec<u8> {
+    // The NULL bitmap of a binary row is offset by two bits.
+    let mut null_bitmap = vec![0u8; (row.len() + 9) / 8];
+    let mut values = Vec::new();
+    for (i, value) in row.iter().enumerate() {
+        if *value == Value::NULL {
+            null_bitmap[(i + 2) / 8] |= 1 << ((i + 2) % 8);
+        } else {
+            value.serialize(&mut values);
+        }
+    }
+    let mut packet = vec![0];
+    packet.extend(null_bitmap);
+    packet.extend(values);
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::Format;
+    use crate::{params, Error, Params, TxOptions};
+    use mysql_async::prelude::*;
+
+    fn values() -> Vec<Value> {
+        vec![
+            Value::Int(-1),
+            Value::UInt(u64::MAX),
+            Value::Double(1.5),
+            Value::NULL,
+            Value::from("text"),
+        ]
+    }
+
+    type Types = (i64, u64, f64, Option<String>, String);
+
+    fn expected() -> Types {
+        (-1, u64::MAX, 1.5, None, "text".to_string())
+    }
+
+    fn server() -> FakeServer {
+        let now = Value::Date(2024, 2, 29, 12, 30, 0, 0);
+        FakeServer::start(
+            Script::new()
+                .with_response(
+                    "SELECT * FROM types",
+                    Response::rows(&["i", "u", "d", "n", "s"], vec![values()]),
+                )
+                .with_response("SELECT NOW()", Response::rows(&["NOW()"], vec![vec![now]])),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_binary_rows() {
+        let server = server();
+        let rows: Vec<Types> = server
+            .database()
+            .select("SELECT * FROM types", None)
+            .await
+            .unwrap();
+        assert_eq!(rows, [expected()]);
+        let now: Value = server
+            .database()
+            .select_scalar("SELECT NOW()", None)
+            .await
+            .unwrap();
+        assert_eq!(now, Value::Date(2024, 2, 29, 12, 30, 0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_text_rows() {
+        let server = server();
+        let mut conn = mysql_async::Conn::from_url(server.url()).await.unwrap();
+        let rows: Vec<Types> = conn.query("SELECT * FROM types").await.unwrap();
+        assert_eq!(rows, [expected()]);
+        let now: Option<String> = conn.query_first("SELECT NOW()").await.unwrap();
+        assert_eq!(now.as_deref(), Some("2024-02-29 12:30:00"));
+        conn.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_params_and_handler() {
+        let server = FakeServer::start(Script::new().with_handler(|query, params| {
+            (query == "INSERT INTO t VALUES (?, ?, ?)").then(|| {
+                Response::Ok(ExecResult {
+                    affected_rows: params.len() as u64,
+                    last_insert_id: Some(7),
+                    ..ExecResult::default()
+                })
+            })
+        }));
+        let params = Params::Positional(values()[..3].to_vec());
+        let result = server
+            .database()
+            .execute("INSERT INTO t VALUES (?, ?, ?)", Some(params))
+            .await
+            .unwrap();
+        assert_eq!(result.affected_rows, 3);
+        assert_eq!(result.last_insert_id, Some(7));
+        assert_eq!(server.received()[0].params, values()[..3]);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_and_errors() {
+        let server = FakeServer::start(
+            Script::new()
+                .with_response("UPDATE t SET a = ?", Response::affected_rows(2))
+                .with_response("DELETE FROM t", Response::error(1213, "Deadlock found")),
+        );
+        let database = server.database();
+        database.ping().await.unwrap();
+
+        let tx = database.begin(TxOptions::default()).await.unwrap();
+        tx.execute("UPDATE t SET a = :a", Some(params! { "a" => 1 }))
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+        let queries = server
+            .received()
+            .into_iter()
+            .map(|received| received.query)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            queries.first().map(String::as_str),
+            Some("START TRANSACTION")
+        );
+        assert_eq!(queries.last().map(String::as_str), Some("COMMIT"));
+
+        let err = database.execute("DELETE FROM t", None).await.unwrap_err();
+        assert!(err.is_deadlock());
+        let err = database.execute("DROP TABLE t", None).await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Server {
+                code: ER_UNKNOWN_ERROR,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mock_executor() {
+        let mock = MockExecutor::new(
+            Script::new()
+                .with_response(
+                    "SELECT * FROM types WHERE id IN (?, ?)",
+                    Response::rows(&["i", "u", "d", "n", "s"], vec![values()]),
+                )
+                .with_response("DELETE FROM t", Response::error(1213, "Deadlock found")),
+        );
+        let rows: Vec<Types> = mock
+            .select(
+                "SELECT * FROM types WHERE id IN (:ids)",
+                Some(params! { "ids" => crate::InList(vec![1, 2]) }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(rows, [expected()]);
+        assert_eq!(mock.received()[0].params, [Value::Int(1), Value::Int(2)]);
+
+        let result = mock
+            .transaction(TxOptions::default(), |tx| {
+                Box::pin(async move { tx.execute("DELETE FROM t", None).await })
+            })
+            .await;
+        assert!(result.unwrap_err().is_deadlock());
+        let err = mock.execute("DROP TABLE t", None).await.unwrap_err();
+        assert_eq!(err.code(), Some(ER_UNKNOWN_ERROR));
+        let queries = mock
+            .received()
+            .into_iter()
+            .map(|received| received.query)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            queries[1..],
+            [
+                "START TRANSACTION",
+                "DELETE FROM t",
+                "ROLLBACK",
+                "DROP TABLE t"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scratch_database() {
+        let server = FakeServer::start(
+            Script::new()
+                .with_response("INSERT INTO t VALUES (1)", Response::affected_rows(1))
+                .with_handler(|query, _| {
+                    let query = query.to_ascii_uppercase();
+                    (query.starts_with("CREATE DATABASE") || query.starts_with("DROP DATABASE"))
+                        .then(Response::ok)
+                }),
+        );
+        let setup = || {
+            TestSetup::new()
+                .with_url(server.url())
+                .with_fixture(Fixture::new("t", Format::Sql, "INSERT INTO t VALUES (1)"))
+        };
+        let affected_rows = setup().run("tests::test_example", |db| async move {
+            db.execute("INSERT INTO t VALUES (1)", None)
+                .await
+                .unwrap()
+                .affected_rows
+        });
+        assert_eq!(affected_rows, 1);
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            setup().run("test_panics", |_| async { panic!("test failed") })
+        }));
+        assert!(panicked.is_err());
+
+        let received = server.received();
+        let queries = received
+            .iter()
+            .map(|received| received.query.as_str())
+            .collect::<Vec<_>>();
+        let name = queries[0]
+            .strip_prefix("CREATE DATABASE `")
+            .and_then(|name| name.strip_suffix('`'))
+            .unwrap();
+        assert!(name.starts_with("test_test_example_") && name.len() <= 64);
+        assert_eq!(
+            queries[1..4],
+            [
+                "INSERT INTO t VALUES (1)",
+                "INSERT INTO t VALUES (1)",
+                format!("DROP DATABASE `{}`", name).as_str(),
+            ]
+        );
+        assert!(queries[4].starts_with("CREATE DATABASE `test_test_panics_"));
+        assert!(queries[6].starts_with("DROP DATABASE `test_test_panics_"));
+    }
+}
@@ -0,0 +1,428 @@
+//! Spans for every query, behind the `tracing` feature.
+//!
+//! Each query runs in an `INFO` span named `mysql.query` with target `mysql::query`, from
+//! checking out a connection to reading the result. Its fields follow the OpenTelemetry
+//! database conventions where one exists: `db.system.name`, `db.namespace`, `db.query.text`,
+//! `db.response.returned_rows`, `db.response.status_code`, `error.type`, `server.address` and
+//! `server.port`, plus `otel.name`, `otel.kind` and `otel.status_code` for
+//! `tracing-opentelemetry`. The rest are MySQL specific: `mysql.connection_id`,
+//! `mysql.parameter_count`, `mysql.affected_rows`, `mysql.pool_wait_ms` and
+//! `mysql.duration_ms`.
+//!
+//! `db.query.text` is normalized to one line without comments and with string and number
+//! literals replaced by `?`. Parameter values are never put on the span, only in a `TRACE`
+//! event inside it, so they stay out of traces unless that level is enabled for
+//! `mysql::query`.
+//!
+//! Without the feature every function here compiles to nothing.
+
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::time::Duration;
+
+use crate::{Params, Result, Value};
+
+#[cfg(feature = "tracing")]
+const TARGET: &str = "mysql::query";
+
+/// The span of a single query.
+pub(crate) struct Query {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl Query {
+    #[cfg(feature = "tracing")]
+    pub(crate) fn new(query: &str) -> Self {
+        let operation = query
+            .trim_start_matches(|c: char| c == '(' || c.is_whitespace())
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .next()
+            .filter(|keyword| !keyword.is_empty())
+            .map_or_else(|| "mysql".to_string(), str::to_ascii_uppercase);
+        let span = tracing::info_span!(
+            target: TARGET,
+            "mysql.query",
+            otel.name = %operation,
+            otel.kind = "client",
+            otel.status_code = tracing::field::Empty,
+            db.system.name = "mysql",
+            db.namespace = tracing::field::Empty,
+            db.query.text = tracing::field::Empty,
+            db.response.returned_rows = tracing::field::Empty,
+            db.response.status_code = tracing::field::Empty,
+            error.type = tracing::field::Empty,
+            server.address = tracing::field::Empty,
+            server.port = tracing::field::Empty,
+            mysql.connection_id = tracing::field::Empty,
+            mysql.parameter_count = tracing::field::Empty,
+            mysql.affected_rows = tracing::field::Empty,
+            mysql.pool_wait_ms = tracing::field::Empty,
+            mysql.duration_ms = tracing::field::Empty,
+        );
+        Query { span }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn new(_query: &str) -> Self {
+        Query {}
+    }
+
+    /// Runs `operation` inside the span, recording how long it took and how it failed.
+    #[cfg(all(feature = "async", feature = "tracing"))]
+    pub(crate) async fn run<T>(self, operation: impl Future<Output = Result<T>>) -> Result<T> {
+        use tracing::Instrument;
+
+        let start = std::time::Instant::now();
+        let result = operation.instrument(self.span.clone()).await;
+        self.finish(start.elapsed(), &result);
+        result
+    }
+
+    #[cfg(all(feature = "async", not(feature = "tracing")))]
+    pub(crate) async fn run<T>(self, operation: impl Future<Output = Result<T>>) -> Result<T> {
+        operation.await
+    }
+
+    /// Like [`Query::run`], for the blocking API.
+    #[cfg(all(feature = "blocking", feature = "tracing"))]
+    pub(crate) fn run_blocking<T>(self, operation: impl FnOnce() -> Result<T>) -> Result<T> {
+        let start = std::time::Instant::now();
+        let result = self.span.in_scope(operation);
+        self.finish(start.elapsed(), &result);
+        result
+    }
+
+    #[cfg(all(feature = "blocking", not(feature = "tracing")))]
+    pub(crate) fn run_blocking<T>(self, operation: impl FnOnce() -> Result<T>) -> Result<T> {
+        operation()
+    }
+
+    #[cfg(feature = "tracing")]
+    fn finish<T>(&self, duration: Duration, result: &Result<T>) {
+        self.span
+            .record("mysql.duration_ms", duration.as_secs_f64() * 1000.0);
+        if let Err(err) = result {
+            self.span.record("otel.status_code", "ERROR");
+            self.span.record("error.type", error_type(err));
+            if let Some(code) = err.code() {
+                self.span
+                    .record("db.response.status_code", code.to_string());
+            }
+        }
+    }
+}
+
+/// A low-cardinality name for the kind of failure, the error code for server errors.
+#[cfg(feature = "tracing")]
+fn error_type(err: &crate::Error) -> String {
+    use crate::Error;
+
+    match err {
+        Error::Server { code, .. } => code.to_string(),
+        Error::Config(_) => "config".to_string(),
+        Error::Io(_) => "io".to_string(),
+        #[cfg(feature = "async")]
+        Error::Driver(_) => "driver".to_string(),
+        Error::FromRow(_) => "from_row".to_string(),
+        Error::Params(_) => "params".to_string(),
+        Error::Serialize(_) => "serialize".to_string(),
+        Error::Deserialize(_) => "deserialize".to_string(),
+        Error::NotFound => "not_found".to_string(),
+        Error::TooManyRows => "too_many_rows".to_string(),
+        Error::Timeout(_) => "timeout".to_string(),
+        #[cfg(feature = "async")]
+        Error::Migrate(_) => "migrate".to_string(),
+        #[cfg(feature = "async")]
+        Error::Fixture(_) => "fixture".to_string(),
+        Error::Query(_) => "query".to_string(),
+        Error::Other(_) => "other".to_string(),
+    }
+}
+
+/// Records the statement about to be sent on the current query span.
+#[cfg(feature = "tracing")]
+pub(crate) fn statement(query: &str, connection_id: u32) {
+    let span = tracing::Span::current();
+    span.record("db.query.text", normalize(query));
+    span.record("mysql.connection_id", connection_id);
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn statement(_query: &str, _connection_id: u32) {}
+
+/// Records how many parameters the statement has, logging their values at `TRACE`.
+#[cfg(feature = "tracing")]
+pub(crate) fn parameters(params: &Params) {
+    match params {
+        Params::Empty => values(&[]),
+        Params::Positional(positional) => values(positional),
+        Params::Named(named) => {
+            tracing::Span::current().record("mysql.parameter_count", named.len());
+            let named = named
+                .iter()
+                .map(|(name, value)| (String::from_utf8_lossy(name), value))
+                .collect::<std::collections::BTreeMap<_, _>>();
+            tracing::trace!(target: TARGET, parameters = ?named, "query parameters");
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn parameters(_params: &Params) {}
+
+/// Like [`parameters`], for positional values.
+#[cfg(feature = "tracing")]
+pub(crate) fn values(values: &[Value]) {
+    tracing::Span::current().record("mysql.parameter_count", values.len());
+    if !values.is_empty() {
+        tracing::trace!(target: TARGET, parameters = ?values, "query parameters");
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn values(_values: &[Value]) {}
+
+/// Records the database and server a connection belongs to on the current query span.
+#[cfg(all(feature = "async", feature = "tracing"))]
+pub(crate) fn server(opts: &mysql_async::Opts) {
+    let span = tracing::Span::current();
+    if let Some(db_name) = opts.db_name() {
+        span.record("db.namespace", db_name);
+    }
+    span.record("server.address", opts.ip_or_hostname());
+    span.record("server.port", opts.tcp_port());
+}
+
+#[cfg(all(feature = "async", not(feature = "tracing")))]
+pub(crate) fn server(_opts: &mysql_async::Opts) {}
+
+/// Logs why a blocking checkout failed, which the returned timeout error does not say.
+#[cfg(all(feature = "blocking", feature = "tracing"))]
+pub(crate) fn checkout_failed(err: &r2d2::Error) {
+    tracing::warn!(target: TARGET, error = %err, "timed out checking out a connection");
+}
+
+#[cfg(all(feature = "blocking", not(feature = "tracing")))]
+pub(crate) fn checkout_failed(_err: &r2d2::Error) {}
+
+/// Records how long checking out a pooled connection took on the current query span.
+#[cfg(feature = "tracing")]
+pub(crate) fn pool_wait(wait: Duration) {
+    tracing::Span::current().record("mysql.pool_wait_ms", wait.as_secs_f64() * 1000.0);
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn pool_wait(_wait: Duration) {}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn returned_rows(rows: usize) {
+    tracing::Span::current().record("db.response.returned_rows", rows);
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn returned_rows(_rows: usize) {}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn affected_rows(rows: u64) {
+    tracing::Span::current().record("mysql.affected_rows", rows);
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn affected_rows(_rows: u64) {}
+
+/// `query` on one line, without comments and with string and number literals replaced by
+/// `?` so that values inlined into the SQL do not end up in traces.
+#[cfg(feature = "tracing")]
+fn normalize(query: &str) -> String {
+    let bytes = query.as_bytes();
+    let skip_to = |from: usize, end: &[u8]| {
+        bytes[from..]
+            .windows(end.len())
+            .position(|window| window == end)
+            .map_or(bytes.len(), |i| from + i + end.len())
+    };
+    let is_word = |c: u8| c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80;
+    let mut normalized = String::with_capacity(query.len());
+    let mut space = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let token = match bytes[i] {
+            c if c.is_ascii_whitespace() => {
+                space = true;
+                i += 1;
+                continue;
+            }
+            b'#' => {
+                i = skip_to(i, b"\n");
+                space = true;
+                continue;
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-')
+                && bytes.get(i + 2).is_none_or(u8::is_ascii_whitespace) =>
+            {
+                i = skip_to(i, b"\n");
+                space = true;
+                continue;
+            }
+            // `/*!` and `/*+` hold executable code and optimizer hints.
+            b'/' if bytes.get(i + 1) == Some(&b'*')
+                && !matches!(bytes.get(i + 2), Some(b'!' | b'+')) =>
+            {
+                i = skip_to(i + 2, b"*/");
+                space = true;
+                continue;
+            }
+            quote @ (b'\'' | b'"' | b'`') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    if bytes[i] == b'\\' && quote != b'`' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i = (i + 1).min(bytes.len());
+                if quote == b'`' {
+                    &query[start..i]
+                } else {
+                    "?"
+                }
+            }
+            b'0'..=b'9' if i == 0 || !is_word(bytes[i - 1]) => {
+                while i < bytes.len() && (is_word(bytes[i]) || bytes[i] == b'.') {
+                    i += 1;
+                }
+                "?"
+            }
+            c if is_word(c) => {
+                while i < bytes.len() && is_word(bytes[i]) {
+                    i += 1;
+                }
+                &query[start..i]
+            }
+            _ => {
+                i += 1;
+                &query[start..i]
+            }
+        };
+        if space && !normalized.is_empty() {
+            normalized.push(' ');
+        }
+        space = false;
+        normalized.push_str(token);
+    }
+    normalized
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use crate::params;
+    use crate::test_support::{FakeServer, Response, Script};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize("SELECT *\n  FROM `users`  -- all of them\nWHERE id = ? # why\n"),
+            "SELECT * FROM `users` WHERE id = ?"
+        );
+        assert_eq!(
+            normalize("SELECT name FROM t2 WHERE name = 'o\\'brien' AND n > -1.5e3 /* x */"),
+            "SELECT name FROM t2 WHERE name = ? AND n > -?"
+        );
+        assert_eq!(
+            normalize("SELECT /*+ MAX_EXECUTION_TIME(100) */ \"é\", `a b` FROM café"),
+            "SELECT /*+ MAX_EXECUTION_TIME(?) */ ?, `a b` FROM café"
+        );
+    }
+
+    /// Collects the fields recorded on `mysql.query` spans.
+    #[derive(Clone, Default)]
+    struct Spans(Arc<Mutex<Vec<HashMap<String, String>>>>);
+
+    struct Fields<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for Spans {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            let mut fields = HashMap::new();
+            attrs.record(&mut Fields(&mut fields));
+            self.0.lock().unwrap().push(fields);
+        }
+
+        fn on_record(&self, _id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            // Queries in these tests run one after another, so the last span is the current one.
+            if let Some(fields) = self.0.lock().unwrap().last_mut() {
+                values.record(&mut Fields(fields));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_span() {
+        let spans = Spans::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+        let server = FakeServer::start(
+            Script::new()
+                .with_response(
+                    "SELECT name\n  FROM users WHERE id > ?",
+                    Response::rows(&["name"], vec![vec!["alice".into()], vec!["bob".into()]]),
+                )
+                .with_response("DELETE FROM users", Response::error(1213, "Deadlock found")),
+        );
+        let database = server.database();
+        let names: Vec<String> = database
+            .select(
+                "SELECT name\n  FROM users WHERE id > :id",
+                Some(params! { "id" => 0 }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(names, ["alice", "bob"]);
+        database
+            .execute("DELETE FROM users", None)
+            .await
+            .unwrap_err();
+
+        let spans = spans.0.lock().unwrap();
+        let [select, delete] = &spans[..] else {
+            panic!("expected two spans, got {:?}", spans);
+        };
+        assert_eq!(select["otel.name"], "SELECT");
+        assert_eq!(select["db.system.name"], "mysql");
+        assert_eq!(select["db.namespace"], "test");
+        assert_eq!(
+            select["db.query.text"],
+            "SELECT name FROM users WHERE id > :id"
+        );
+        assert_eq!(select["mysql.parameter_count"], "1");
+        assert_eq!(select["db.response.returned_rows"], "2");
+        assert!(select.contains_key("mysql.connection_id"));
+        assert!(select.contains_key("mysql.pool_wait_ms"));
+        assert!(select.contains_key("mysql.duration_ms"));
+        assert!(!select.contains_key("otel.status_code"));
+
+        assert_eq!(delete["otel.name"], "DELETE");
+        assert_eq!(delete["otel.status_code"], "ERROR");
+        assert_eq!(delete["db.response.status_code"], "1213");
+        assert_eq!(delete["error.type"], "1213");
+    }
+}
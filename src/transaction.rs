@@ -0,0 +1,345 @@
+#[cfg(feature = "async")]
+use futures::lock::Mutex;
+#[cfg(feature = "async")]
+use futures::AsyncRead;
+#[cfg(feature = "async")]
+use mysql_async::{prelude::*, TxOpts};
+#[cfg(feature = "async")]
+use serde::de::DeserializeOwned;
+use std::fmt;
+#[cfg(feature = "async")]
+use std::time::Duration;
+
+#[cfg(feature = "async")]
+use crate::exec;
+#[cfg(feature = "async")]
+use crate::infile::{self, LoadDataOptions};
+#[cfg(feature = "async")]
+use crate::result::{self, ExecResult};
+#[cfg(feature = "async")]
+use crate::{rt, trace, Params, Result, Value};
+
+/// The isolation level a transaction is started with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl fmt::Display for IsolationLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsolationLevel::ReadUncommitted => write!(f, "READ UNCOMMITTED"),
+            IsolationLevel::ReadCommitted => write!(f, "READ COMMITTED"),
+            IsolationLevel::RepeatableRead => write!(f, "REPEATABLE READ"),
+            IsolationLevel::Serializable => write!(f, "SERIALIZABLE"),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl From<IsolationLevel> for mysql_async::IsolationLevel {
+    fn from(isolation_level: IsolationLevel) -> Self {
+        match isolation_level {
+            IsolationLevel::ReadUncommitted => mysql_async::IsolationLevel::ReadUncommitted,
+            IsolationLevel::ReadCommitted => mysql_async::IsolationLevel::ReadCommitted,
+            IsolationLevel::RepeatableRead => mysql_async::IsolationLevel::RepeatableRead,
+            IsolationLevel::Serializable => mysql_async::IsolationLevel::Serializable,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TxOptions {
+    isolation_level: Option<IsolationLevel>,
+    readonly: Option<bool>,
+    consistent_snapshot: bool,
+    pub(crate) retries: usize,
+}
+
+impl Default for TxOptions {
+    fn default() -> Self {
+        TxOptions {
+            isolation_level: None,
+            readonly: None,
+            consistent_snapshot: false,
+            retries: 3,
+        }
+    }
+}
+
+impl TxOptions {
+    pub fn with_isolation_level(mut self, isolation_level: IsolationLevel) -> Self {
+        self.isolation_level = Some(isolation_level);
+        self
+    }
+
+    pub fn with_readonly(mut self, readonly: bool) -> Self {
+        self.readonly = Some(readonly);
+        self
+    }
+
+    /// Starts the transaction `WITH CONSISTENT SNAPSHOT`.
+    pub fn with_consistent_snapshot(mut self, consistent_snapshot: bool) -> Self {
+        self.consistent_snapshot = consistent_snapshot;
+        self
+    }
+
+    /// How many times [`Database::transaction`](crate::Database::transaction) reruns a transaction that failed with a
+    /// deadlock or lock wait timeout. Defaults to 3.
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn tx_opts(&self) -> TxOpts {
+        let mut opts = TxOpts::new();
+        opts.with_isolation_level(self.isolation_level.map(Into::into))
+            .with_readonly(self.readonly)
+            .with_consistent_snapshot(self.consistent_snapshot);
+        opts
+    }
+
+    /// The statements that start the transaction, for drivers without a transaction API.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn begin_statements(&self) -> Vec<String> {
+        let mut statements = Vec::new();
+        if let Some(isolation_level) = self.isolation_level {
+            statements.push(format!(
+                "SET TRANSACTION ISOLATION LEVEL {}",
+                isolation_level
+            ));
+        }
+        match self.readonly {
+            Some(true) => statements.push("SET TRANSACTION READ ONLY".to_string()),
+            Some(false) => statements.push("SET TRANSACTION READ WRITE".to_string()),
+            None => {}
+        }
+        statements.push(if self.consistent_snapshot {
+            "START TRANSACTION WITH CONSISTENT SNAPSHOT".to_string()
+        } else {
+            "START TRANSACTION".to_string()
+        });
+        statements
+    }
+}
+
+/// A transaction holding a single pooled connection.
+///
+/// The transaction is rolled back if it is dropped without calling [`Transaction::commit`].
+#[cfg(feature = "async")]
+pub struct Transaction {
+    inner: Mutex<mysql_async::Transaction<'static>>,
+    timeout: Option<Duration>,
+    max_list_len: Option<usize>,
+}
+
+#[cfg(feature = "async")]
+impl Transaction {
+    pub(crate) fn new(
+        inner: mysql_async::Transaction<'static>,
+        timeout: Option<Duration>,
+        max_list_len: Option<usize>,
+    ) -> Self {
+        Transaction {
+            inner: Mutex::new(inner),
+            timeout,
+            max_list_len,
+        }
+    }
+
+    pub async fn select<T: FromRow + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<Vec<T>> {
+        trace::Query::new(query)
+            .run(exec::with_timeout(self.timeout, async {
+                let mut tx = self.inner.lock().await;
+                exec::select(&mut *tx, query, params_map, self.max_list_len).await
+            }))
+            .await
+    }
+
+    /// Like [`Transaction::select`], but deserializes each row with serde instead of `FromRow`.
+    pub async fn select_as<T: DeserializeOwned + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<Vec<T>> {
+        trace::Query::new(query)
+            .run(exec::with_timeout(self.timeout, async {
+                let mut tx = self.inner.lock().await;
+                exec::select_as(&mut *tx, query, params_map, self.max_list_len).await
+            }))
+            .await
+    }
+
+    /// Returns the only row of the result, failing with [`Error::NotFound`](crate::Error::NotFound)
+    /// or [`Error::TooManyRows`](crate::Error::TooManyRows) otherwise.
+    pub async fn select_one<T: FromRow + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<T> {
+        result::one(self.select(query, params_map).await?)
+    }
+
+    /// Returns the only row of the result if there is one, failing with
+    /// [`Error::TooManyRows`](crate::Error::TooManyRows) if there are several.
+    pub async fn select_optional<T: FromRow + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<Option<T>> {
+        result::optional(self.select(query, params_map).await?)
+    }
+
+    /// Returns the first column of the only row, e.g. for `SELECT COUNT(*)`.
+    pub async fn select_scalar<T: FromValue + Send>(
+        &self,
+        query: &str,
+        params_map: Option<Params>,
+    ) -> Result<T> {
+        let (value,) = self.select_one::<(T,)>(query, params_map).await?;
+        Ok(value)
+    }
+
+    pub async fn execute(&self, query: &str, params_map: Option<Params>) -> Result<ExecResult> {
+        trace::Query::new(query)
+            .run(exec::with_timeout(self.timeout, async {
+                let mut tx = self.inner.lock().await;
+                exec::execute(&mut *tx, query, params_map, self.max_list_len).await
+            }))
+            .await
+    }
+
+    /// Executes `query` once for every set of parameters, preparing it only once. Returns the
+    /// total number of affected rows.
+    pub async fn execute_batch<I>(&self, query: &str, params: I) -> Result<u64>
+    where
+        I: IntoIterator<Item = Params> + Send,
+        I::IntoIter: Send,
+    {
+        trace::Query::new(query)
+            .run(exec::with_timeout(self.timeout, async {
+                let mut tx = self.inner.lock().await;
+                exec::execute_batch(&mut *tx, query, params, self.max_list_len).await
+            }))
+            .await
+    }
+
+    /// Inserts `rows` into `table` with multi-row `INSERT` statements that each fit in
+    /// `max_allowed_packet`. Returns the total number of affected rows.
+    pub async fn bulk_insert<I>(&self, table: &str, columns: &[&str], rows: I) -> Result<u64>
+    where
+        I: IntoIterator<Item = Vec<Value>> + Send,
+        I::IntoIter: Send,
+    {
+        trace::Query::new("INSERT")
+            .run(exec::with_timeout(self.timeout, async {
+                let mut tx = self.inner.lock().await;
+                exec::bulk_insert(&mut *tx, table, columns, rows).await
+            }))
+            .await
+    }
+
+    /// Loads `reader` into `table` with `LOAD DATA LOCAL INFILE`, the fastest way to import
+    /// data. The data must be in the format described by `options`, and `columns` lists the
+    /// columns of each line, or is empty to load every column of the table in order.
+    pub async fn load_data<R>(
+        &self,
+        table: &str,
+        columns: &[&str],
+        reader: R,
+        options: LoadDataOptions,
+    ) -> Result<ExecResult>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        trace::Query::new("LOAD DATA")
+            .run(exec::with_timeout(self.timeout, async {
+                let mut tx = self.inner.lock().await;
+                let data = infile::from_reader(reader);
+                exec::load_data(&mut *tx, table, columns, data, &options).await
+            }))
+            .await
+    }
+
+    /// Like [`Transaction::load_data`], but encodes `rows` in the format described by `options`.
+    pub async fn load_rows<I>(
+        &self,
+        table: &str,
+        columns: &[&str],
+        rows: I,
+        options: LoadDataOptions,
+    ) -> Result<ExecResult>
+    where
+        I: IntoIterator<Item = Vec<Value>>,
+        I::IntoIter: Send + 'static,
+    {
+        let data = infile::from_rows(rows, options.clone());
+        trace::Query::new("LOAD DATA")
+            .run(exec::with_timeout(self.timeout, async {
+                let mut tx = self.inner.lock().await;
+                exec::load_data(&mut *tx, table, columns, data, &options).await
+            }))
+            .await
+    }
+
+    pub async fn commit(self) -> Result<()> {
+        rt::compat(self.inner.into_inner().commit()).await?;
+        Ok(())
+    }
+
+    pub async fn rollback(self) -> Result<()> {
+        rt::compat(self.inner.into_inner().rollback()).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_tx_opts() {
+        let opts = TxOptions::default()
+            .with_isolation_level(IsolationLevel::Serializable)
+            .with_readonly(true)
+            .with_consistent_snapshot(true)
+            .tx_opts();
+        assert_eq!(
+            opts.isolation_level(),
+            Some(mysql_async::IsolationLevel::Serializable)
+        );
+        assert_eq!(opts.readonly(), Some(true));
+        assert!(opts.consistent_snapshot());
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_begin_statements() {
+        assert_eq!(
+            TxOptions::default().begin_statements(),
+            ["START TRANSACTION"]
+        );
+        let statements = TxOptions::default()
+            .with_isolation_level(IsolationLevel::ReadCommitted)
+            .with_readonly(false)
+            .with_consistent_snapshot(true)
+            .begin_statements();
+        assert_eq!(
+            statements,
+            [
+                "SET TRANSACTION ISOLATION LEVEL READ COMMITTED",
+                "SET TRANSACTION READ WRITE",
+                "START TRANSACTION WITH CONSISTENT SNAPSHOT",
+            ]
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use mysql::{FromRow, Row};
+use mysql_common::constants::ColumnType;
+use mysql_common::packets::Column;
+use mysql_common::row::new_row;
+use mysql_common::value::Value;
+use std::sync::Arc;
+
+fn row(values: Vec<(&str, Value)>) -> Row {
+    let columns = values
+        .iter()
+        .map(|(name, _)| Column::new(ColumnType::MYSQL_TYPE_VAR_STRING).with_name(name.as_bytes()))
+        .collect::<Vec<_>>();
+    new_row(
+        values.into_iter().map(|(_, value)| value).collect(),
+        Arc::from(columns),
+    )
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+struct User {
+    id: i32,
+    #[mysql(rename = "user_name")]
+    name: String,
+    email: Option<String>,
+    #[mysql(default)]
+    active: bool,
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+struct Audit {
+    created_by: String,
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+struct Post {
+    id: u64,
+    #[mysql(try_from = "i64")]
+    views: u32,
+    #[mysql(flatten)]
+    audit: Audit,
+    r#type: String,
+}
+
+#[test]
+fn test_columns_by_name() {
+    let user = User::from_row(row(vec![
+        ("email", Value::NULL),
+        ("user_name", Value::from("alice")),
+        ("id", Value::Int(1)),
+        ("active", Value::Int(1)),
+    ]));
+    assert_eq!(
+        user,
+        User {
+            id: 1,
+            name: "alice".to_string(),
+            email: None,
+            active: true,
+        }
+    );
+}
+
+#[test]
+fn test_default() {
+    let user = User::from_row(row(vec![
+        ("id", Value::Int(1)),
+        ("user_name", Value::from("alice")),
+        ("email", Value::from("alice@example.com")),
+    ]));
+    assert!(!user.active);
+    assert_eq!(user.email.as_deref(), Some("alice@example.com"));
+
+    let user = User::from_row(row(vec![
+        ("id", Value::Int(1)),
+        ("user_name", Value::from("alice")),
+        ("email", Value::NULL),
+        ("active", Value::NULL),
+    ]));
+    assert!(!user.active);
+}
+
+#[test]
+fn test_flatten_and_try_from() {
+    let post = Post::from_row(row(vec![
+        ("id", Value::UInt(7)),
+        ("views", Value::Int(42)),
+        ("created_by", Value::from("bob")),
+        ("type", Value::from("article")),
+    ]));
+    assert_eq!(
+        post,
+        Post {
+            id: 7,
+            views: 42,
+            audit: Audit {
+                created_by: "bob".to_string(),
+            },
+            r#type: "article".to_string(),
+        }
+    );
+}
+
+#[test]
+fn test_errors() {
+    let missing = User::from_row_opt(row(vec![("id", Value::Int(1))]));
+    assert!(missing.is_err());
+
+    let out_of_range = Post::from_row_opt(row(vec![
+        ("id", Value::UInt(7)),
+        ("views", Value::Int(-1)),
+        ("created_by", Value::from("bob")),
+        ("type", Value::from("article")),
+    ]));
+    let err = out_of_range.unwrap_err();
+    assert_eq!(err.0.len(), 4);
+}
+
+#[test]
+fn test_compile_fail() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
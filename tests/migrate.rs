@@ -0,0 +1,118 @@
+use mysql::migrate::{MigrateError, Migration, MigrationState, Migrator};
+use mysql::test_support::{FakeServer, Response, Script};
+use mysql::{Error, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[test]
+fn test_embed_migrations() {
+    let migrator = mysql::embed_migrations!("tests/migrations");
+    let migrations = migrator.migrations();
+    assert_eq!(
+        migrations
+            .iter()
+            .map(|migration| (migration.version, migration.name.as_ref()))
+            .collect::<Vec<_>>(),
+        [(1, "create_users"), (2, "add_email")]
+    );
+    assert_eq!(migrations[0].down.as_deref(), Some("DROP TABLE users;\n"));
+    assert!(migrations[1].down.is_none());
+
+    let from_dir =
+        Migrator::from_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/migrations")).unwrap();
+    assert_eq!(from_dir.migrations(), migrations);
+}
+
+/// Scripts `_migrations` as a table kept in memory, and accepts any other statement.
+fn migrations_server() -> FakeServer {
+    let table = Arc::new(Mutex::new(Vec::<Vec<Value>>::new()));
+    FakeServer::start(Script::new().with_handler(move |query, params| {
+        let mut table = table.lock().unwrap();
+        if query.starts_with("SELECT version, name, checksum, success FROM _migrations") {
+            return Some(Response::rows(
+                &["version", "name", "checksum", "success"],
+                table.clone(),
+            ));
+        }
+        if query.starts_with("INSERT INTO _migrations") {
+            table.push(params.to_vec());
+        } else if query.starts_with("UPDATE _migrations SET success = TRUE") {
+            let row = table.iter_mut().find(|row| row[0] == params[1])?;
+            row[3] = Value::Int(1);
+        } else if query.starts_with("UPDATE _migrations SET success = FALSE") {
+            let row = table.iter_mut().find(|row| row[0] == params[0])?;
+            row[3] = Value::Int(0);
+        } else if query.starts_with("DELETE FROM _migrations") {
+            table.retain(|row| row[0] != params[0]);
+        } else if query.starts_with("SELECT GET_LOCK") {
+            return Some(Response::rows(&["lock"], vec![vec![Value::Int(1)]]));
+        }
+        Some(Response::ok())
+    }))
+}
+
+#[tokio::test]
+async fn test_run_and_undo() {
+    let server = migrations_server();
+    let database = server.database();
+    let migrator = mysql::embed_migrations!("tests/migrations");
+
+    assert_eq!(migrator.run(&database).await.unwrap(), [1, 2]);
+    assert!(migrator.run(&database).await.unwrap().is_empty());
+    let states = migrator
+        .status(&database)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|status| status.state)
+        .collect::<Vec<_>>();
+    assert_eq!(states, [MigrationState::Applied, MigrationState::Applied]);
+
+    // Migration 2 has no down script.
+    let err = migrator.undo(&database, 0).await.unwrap_err();
+    assert!(matches!(err, Error::Migrate(MigrateError::Irreversible(2))));
+
+    let edited = Migrator::new(vec![
+        Migration::new(
+            1,
+            "create_users",
+            "CREATE TABLE people (id INT)",
+            None::<&str>,
+        ),
+        migrator.migrations()[1].clone(),
+    ])
+    .unwrap();
+    let err = edited.run(&database).await.unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Migrate(MigrateError::Changed { version: 1, .. })
+    ));
+
+    let received = server.received();
+    assert!(received
+        .iter()
+        .any(|received| received.query.starts_with("CREATE TABLE users")));
+}
+
+#[tokio::test]
+async fn test_lock() {
+    let lock_server = |acquired: Value| {
+        FakeServer::start(Script::new().with_handler(move |query, _| {
+            query
+                .starts_with("SELECT GET_LOCK")
+                .then(|| Response::rows(&["lock"], vec![vec![acquired.clone()]]))
+        }))
+    };
+    let migrator =
+        mysql::embed_migrations!("tests/migrations").with_lock_timeout(Duration::from_millis(500));
+
+    let server = lock_server(Value::Int(0));
+    let err = migrator.run(&server.database()).await.unwrap_err();
+    assert!(matches!(err, Error::Timeout(timeout) if timeout == Duration::from_millis(500)));
+    // Sub-second timeouts are not truncated to zero.
+    assert_eq!(server.received()[0].params, [Value::Double(0.5)]);
+
+    let server = lock_server(Value::NULL);
+    let err = migrator.run(&server.database()).await.unwrap_err();
+    assert!(matches!(err, Error::Migrate(MigrateError::Lock)));
+}
@@ -0,0 +1,44 @@
+// Exercises the driver under each enabled runtime without a MySQL server: a refused
+// connection needs the reactor and a stalled handshake needs the timer.
+#![cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+
+use mysql::{Database, Error, Result};
+use std::net::TcpListener;
+use std::time::Duration;
+
+async fn check_runtime() {
+    // Nothing listens on port 1, so connecting is refused.
+    let database = Database::from_url("mysql://root@127.0.0.1:1/test").unwrap();
+    let result: Result<()> = database.ping().await;
+    assert!(matches!(result, Err(Error::Io(_))), "{:?}", result);
+
+    // The listener accepts the TCP connection but never sends a handshake.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "mysql://root@127.0.0.1:{}/test",
+        listener.local_addr().unwrap().port()
+    );
+    let database = Database::from_url(&url)
+        .unwrap()
+        .with_timeout(Duration::from_millis(100));
+    let result = database.ping().await;
+    assert!(matches!(result, Err(Error::Timeout(_))), "{:?}", result);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_tokio() {
+    check_runtime().await;
+}
+
+#[cfg(feature = "async-std")]
+#[test]
+fn test_async_std() {
+    async_std::task::block_on(check_runtime());
+}
+
+#[cfg(feature = "smol")]
+#[test]
+fn test_smol() {
+    smol::block_on(check_runtime());
+}
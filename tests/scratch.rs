@@ -0,0 +1,25 @@
+// `#[mysql::test]` needs a real server, so these only run with `DATABASE_URL` set and
+// `cargo test -- --ignored`. The scratch database setup itself is covered against the fake
+// server in `mysql::test_support`.
+
+use mysql::{Database, Result};
+
+#[mysql::test(migrations = "tests/migrations", fixtures("tests/fixtures/users.yaml"))]
+#[ignore]
+async fn test_migrations_and_fixtures(db: Database) {
+    let names: Vec<String> = db
+        .select("SELECT name FROM users ORDER BY id", None)
+        .await
+        .unwrap();
+    assert_eq!(names, ["alice", "bob"]);
+}
+
+#[mysql::test]
+#[ignore]
+async fn test_own_database(db: Database) -> Result<()> {
+    let name: Option<String> = db.select_scalar("SELECT DATABASE()", None).await?;
+    assert!(name.unwrap().starts_with("test_test_own_database_"));
+    let tables: Vec<String> = db.select("SHOW TABLES", None).await?;
+    assert!(tables.is_empty());
+    Ok(())
+}
@@ -0,0 +1,9 @@
+use mysql::FromRow;
+
+#[derive(FromRow)]
+enum Status {
+    Active,
+    Inactive,
+}
+
+fn main() {}
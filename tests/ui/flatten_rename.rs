@@ -0,0 +1,15 @@
+use mysql::FromRow;
+
+#[derive(FromRow)]
+struct Audit {
+    created_by: String,
+}
+
+#[derive(FromRow)]
+struct Post {
+    id: i32,
+    #[mysql(flatten, rename = "audit")]
+    audit: Audit,
+}
+
+fn main() {}
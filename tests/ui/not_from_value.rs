@@ -0,0 +1,11 @@
+use mysql::FromRow;
+
+struct Email(String);
+
+#[derive(FromRow)]
+struct User {
+    id: i32,
+    email: Email,
+}
+
+fn main() {}
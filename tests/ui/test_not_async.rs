@@ -0,0 +1,4 @@
+#[mysql::test]
+fn test_sync(db: mysql::Database) {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use mysql::FromRow;
+
+#[derive(FromRow)]
+struct Pair(i32, String);
+
+fn main() {}
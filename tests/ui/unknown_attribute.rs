@@ -0,0 +1,9 @@
+use mysql::FromRow;
+
+#[derive(FromRow)]
+struct User {
+    #[mysql(column = "user_id")]
+    id: i32,
+}
+
+fn main() {}